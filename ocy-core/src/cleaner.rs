@@ -1,3 +1,5 @@
+use std::{path::Path, sync::Mutex, thread};
+
 use crate::{
     command::CommandExecutor,
     filesystem::FileSystemClean,
@@ -6,6 +8,9 @@ use crate::{
 use eyre::Report;
 use eyre::Result;
 
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
 pub struct Cleaner<CS, CE, FS, N>
 where
     CS: IntoIterator<Item = RemovalCandidate>,
@@ -17,13 +22,34 @@ where
     fs: FS,
     command_executor: CE,
     notifier: N,
+    jobs: usize,
 }
 
-pub trait CleanerNotifier {
+/// Notifications may come from several threads when cleaning concurrently,
+/// those of a given candidate always come from the same thread, in order.
+pub trait CleanerNotifier: Sync {
     fn notify_removal_started(&self, candidate: &RemovalCandidate);
-    fn notify_removal_success(&self, candidate: RemovalCandidate);
+    /// `freed` is the number of bytes the candidate freed, when known, `0`
+    /// when it was moved out of the way.
+    fn notify_removal_success(&self, candidate: RemovalCandidate, freed: Option<u64>);
     fn notify_removal_failed(&self, candidate: RemovalCandidate, report: Report);
-    fn notify_removal_finish(&self);
+    fn notify_removal_finish(&self, summary: &CleanSummary);
+}
+
+/// Space freed by a clean.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CleanSummary {
+    /// Bytes freed by the candidates cleaned, as far as they are known.
+    pub freed: u64,
+    /// Whether some candidates freed an unknown amount.
+    pub has_unknown: bool,
+    /// Bytes moved out of the way, e.g. to the trash, which frees nothing.
+    pub moved: u64,
+    /// Growth of the space available on the file systems cleaned, when it
+    /// can be measured. Lower than `freed` when other processes write
+    /// meanwhile.
+    pub disk_freed: Option<u64>,
 }
 
 impl<CS, CE, FS, N> Cleaner<CS, CE, FS, N>
@@ -39,34 +65,308 @@ where
             fs,
             command_executor,
             notifier,
+            jobs: 1,
         }
     }
 
-    pub fn clean(self) {
-        for candidate in self.candidates {
-            self.notifier.notify_removal_started(&candidate);
-            match clean_candidate(&self.fs, &self.command_executor, &candidate) {
-                Ok(_) => {
-                    self.notifier.notify_removal_success(candidate);
+    /// Cleans up to `jobs` candidates at once, in no particular order. The
+    /// growth of the available disk space is not measured then, since
+    /// concurrent cleans would count each other's.
+    pub fn with_jobs(self, jobs: usize) -> Self {
+        Self { jobs, ..self }
+    }
+
+    pub fn clean(self)
+    where
+        CS::IntoIter: Send,
+    {
+        let Self {
+            candidates,
+            fs,
+            command_executor,
+            notifier,
+            jobs,
+        } = self;
+        let candidates = Mutex::new(candidates.into_iter());
+        let summary = Mutex::new(CleanSummary::default());
+        let measure_disk = jobs <= 1;
+
+        let work = || loop {
+            // Candidates are pulled one at a time, the iterator may decide
+            // whether to go on from what was cleaned so far.
+            let next = candidates.lock().unwrap().next();
+            let Some(candidate) = next else {
+                return;
+            };
+            notifier.notify_removal_started(&candidate);
+            let disk = disk_of(&candidate);
+            let available_before = fs.available_space(disk).filter(|_| measure_disk);
+            match clean_candidate(&fs, &command_executor, &candidate) {
+                Ok(freed) => {
+                    let available_after = fs.available_space(disk).filter(|_| measure_disk);
+                    let mut summary = summary.lock().unwrap();
+                    summary.freed += freed.unwrap_or(0);
+                    summary.has_unknown |= freed.is_none();
+                    if fs.moves_files() {
+                        summary.moved += candidate.file_size().unwrap_or(0);
+                    }
+                    if let Some((before, after)) = available_before.zip(available_after) {
+                        let disk_freed = summary.disk_freed.get_or_insert(0);
+                        *disk_freed += after.saturating_sub(before);
+                    }
+                    drop(summary);
+                    notifier.notify_removal_success(candidate, freed);
                 }
                 Err(report) => {
-                    self.notifier.notify_removal_failed(candidate, report);
+                    notifier.notify_removal_failed(candidate, report);
                 }
             }
-        }
-        self.notifier.notify_removal_finish();
+        };
+        thread::scope(|scope| {
+            for _ in 1..jobs {
+                scope.spawn(work);
+            }
+            work();
+        });
+
+        notifier.notify_removal_finish(&summary.into_inner().unwrap());
     }
 }
 
+/// Returns the bytes freed, as measured by the walk for removals and around
+/// their execution for commands. Moving files frees nothing.
 fn clean_candidate(
     fs: &impl FileSystemClean,
     command_executor: &impl CommandExecutor,
     candidate: &RemovalCandidate,
-) -> Result<()> {
+) -> Result<Option<u64>> {
     match &candidate.action {
-        RemovalAction::Delete { file_info, .. } => fs.remove_file(file_info),
+        RemovalAction::Delete {
+            file_info,
+            file_size,
+        } => {
+            fs.remove_candidate(candidate, file_info)?;
+            Ok(if fs.moves_files() {
+                Some(0)
+            } else {
+                *file_size
+            })
+        }
         RemovalAction::RunCommand { work_dir, command } => {
-            command_executor.execute_command(work_dir, command)
+            let before = fs.measure_size(work_dir);
+            command_executor.execute_command(work_dir, command)?;
+            let after = fs.measure_size(work_dir);
+            Ok(before
+                .zip(after)
+                .map(|(before, after)| before.saturating_sub(after)))
         }
     }
 }
+
+/// A path on the file system a candidate frees space on, which still exists
+/// once it is cleaned.
+fn disk_of(candidate: &RemovalCandidate) -> &Path {
+    let path = &candidate.target().path;
+    match &candidate.action {
+        RemovalAction::Delete { .. } => path.parent().unwrap_or(path),
+        RemovalAction::RunCommand { .. } => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Mutex};
+
+    use eyre::{Report, Result};
+
+    use crate::{
+        command::CommandExecutor,
+        filesystem::FileSystemClean,
+        models::{FileInfo, RemovalCandidate, SimpleFileKind},
+    };
+
+    use super::{CleanSummary, Cleaner, CleanerNotifier};
+
+    /// Pretends commands shrink their work dir from 100 to 30 bytes, and
+    /// every clean frees 10 bytes of disk.
+    #[derive(Default)]
+    struct ShrinkingFS {
+        measures: Mutex<Vec<u64>>,
+        available: Mutex<u64>,
+    }
+
+    impl FileSystemClean for &ShrinkingFS {
+        fn remove_file(&self, _file: &FileInfo) -> Result<()> {
+            *self.available.lock().unwrap() += 10;
+            Ok(())
+        }
+
+        fn measure_size(&self, _file: &FileInfo) -> Option<u64> {
+            self.measures.lock().unwrap().pop()
+        }
+
+        fn available_space(&self, _path: &Path) -> Option<u64> {
+            Some(*self.available.lock().unwrap())
+        }
+    }
+
+    impl CommandExecutor for &ShrinkingFS {
+        fn execute_command(&self, _work_dir: &FileInfo, _command: &str) -> Result<()> {
+            *self.available.lock().unwrap() += 10;
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingNotifier {
+        freed: Mutex<Vec<Option<u64>>>,
+        summary: Mutex<CleanSummary>,
+    }
+
+    impl CleanerNotifier for &RecordingNotifier {
+        fn notify_removal_started(&self, _candidate: &RemovalCandidate) {}
+
+        fn notify_removal_success(&self, _candidate: RemovalCandidate, freed: Option<u64>) {
+            self.freed.lock().unwrap().push(freed);
+        }
+
+        fn notify_removal_failed(&self, _candidate: RemovalCandidate, _report: Report) {}
+
+        fn notify_removal_finish(&self, summary: &CleanSummary) {
+            *self.summary.lock().unwrap() = *summary;
+        }
+    }
+
+    #[test]
+    fn measures_what_candidates_free() {
+        let dir = |path: &str| FileInfo::new(path.into(), "".into(), SimpleFileKind::Directory);
+        let candidates = vec![
+            RemovalCandidate::new("Cargo".into(), dir("/p/target"), Some(42)),
+            RemovalCandidate::new_cmd("Make".into(), dir("/q"), "make clean".into()),
+            RemovalCandidate::new("Gradle".into(), dir("/r/build"), None),
+        ];
+        let fs = ShrinkingFS {
+            measures: Mutex::new(vec![30, 100]),
+            ..ShrinkingFS::default()
+        };
+        let notifier = RecordingNotifier::default();
+
+        Cleaner::new(candidates, &fs, &fs, &notifier).clean();
+
+        assert_eq!(
+            *notifier.freed.lock().unwrap(),
+            vec![Some(42), Some(70), None]
+        );
+        assert_eq!(
+            *notifier.summary.lock().unwrap(),
+            CleanSummary {
+                freed: 112,
+                has_unknown: true,
+                moved: 0,
+                disk_freed: Some(30),
+            }
+        );
+    }
+
+    #[derive(Default)]
+    struct OrderNotifier {
+        events: Mutex<Vec<(String, bool)>>,
+        summary: Mutex<CleanSummary>,
+    }
+
+    impl CleanerNotifier for &OrderNotifier {
+        fn notify_removal_started(&self, candidate: &RemovalCandidate) {
+            let name = candidate.target().name.clone();
+            self.events.lock().unwrap().push((name, false));
+        }
+
+        fn notify_removal_success(&self, candidate: RemovalCandidate, _freed: Option<u64>) {
+            let name = candidate.target().name.clone();
+            self.events.lock().unwrap().push((name, true));
+        }
+
+        fn notify_removal_failed(&self, _candidate: RemovalCandidate, _report: Report) {}
+
+        fn notify_removal_finish(&self, summary: &CleanSummary) {
+            *self.summary.lock().unwrap() = *summary;
+        }
+    }
+
+    #[test]
+    fn cleans_concurrently_notifying_each_candidate_in_order() {
+        let candidates: Vec<_> = (0..20)
+            .map(|i| {
+                let name = format!("target{}", i);
+                let file = FileInfo::new(
+                    format!("/p/{}", name).into(),
+                    name,
+                    SimpleFileKind::Directory,
+                );
+                RemovalCandidate::new("Cargo".into(), file, Some(1))
+            })
+            .collect();
+        let fs = ShrinkingFS::default();
+        let notifier = OrderNotifier::default();
+
+        Cleaner::new(candidates, &fs, &fs, &notifier)
+            .with_jobs(4)
+            .clean();
+
+        let events = notifier.events.into_inner().unwrap();
+        assert_eq!(events.len(), 40);
+        for i in 0..20 {
+            let name = format!("target{}", i);
+            let started = events.iter().position(|e| *e == (name.clone(), false));
+            let finished = events.iter().position(|e| *e == (name.clone(), true));
+            assert!(started.unwrap() < finished.unwrap());
+        }
+        assert_eq!(
+            notifier.summary.into_inner().unwrap(),
+            CleanSummary {
+                freed: 20,
+                has_unknown: false,
+                moved: 0,
+                disk_freed: None,
+            }
+        );
+    }
+
+    /// Moves files elsewhere on the same disk.
+    struct MovingFS;
+
+    impl FileSystemClean for MovingFS {
+        fn remove_file(&self, _file: &FileInfo) -> Result<()> {
+            Ok(())
+        }
+
+        fn moves_files(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn moves_free_nothing() {
+        let dir = |path: &str| FileInfo::new(path.into(), "".into(), SimpleFileKind::Directory);
+        let candidates = vec![
+            RemovalCandidate::new("Cargo".into(), dir("/p/target"), Some(42)),
+            RemovalCandidate::new("Gradle".into(), dir("/r/build"), Some(8)),
+        ];
+        let fs = ShrinkingFS::default();
+        let notifier = RecordingNotifier::default();
+
+        Cleaner::new(candidates, MovingFS, &fs, &notifier)
+            .with_jobs(2)
+            .clean();
+
+        assert_eq!(*notifier.freed.lock().unwrap(), vec![Some(0), Some(0)]);
+        assert_eq!(
+            *notifier.summary.lock().unwrap(),
+            CleanSummary {
+                freed: 0,
+                has_unknown: false,
+                moved: 50,
+                disk_freed: None,
+            }
+        );
+    }
+}
@@ -1,34 +1,62 @@
-use std::{process::Command, thread::sleep, time::Duration};
+use std::{
+    io,
+    process::{Command, Output, Stdio},
+};
 
 use crate::models::FileInfo;
 
-use eyre::{Context, Result};
+use eyre::{bail, Context, Result};
 
-pub trait CommandExecutor {
+/// Shared between threads when cleaning concurrently.
+pub trait CommandExecutor: Sync {
     fn execute_command(&self, work_dir: &FileInfo, command: &str) -> Result<()>;
 }
 
-pub struct MockCommandExecutor;
+#[derive(Default)]
+pub struct RealCommandExecutor {
+    capture_output: bool,
+}
 
-impl CommandExecutor for MockCommandExecutor {
-    fn execute_command(&self, _work_dir: &FileInfo, _command: &str) -> Result<()> {
-        sleep(Duration::from_secs(2));
-        Ok(())
+impl RealCommandExecutor {
+    /// Executor collecting the output of commands instead of printing it, for
+    /// UIs owning the terminal. The output is reported when a command fails.
+    pub fn capturing() -> Self {
+        Self {
+            capture_output: true,
+        }
     }
 }
 
-pub struct RealCommandExecutor;
-
 impl CommandExecutor for RealCommandExecutor {
     fn execute_command(&self, work_dir: &FileInfo, command: &str) -> Result<()> {
         let mut iter = command.split_ascii_whitespace();
         let cmd = iter.next().unwrap();
 
-        Command::new(cmd)
-            .current_dir(&work_dir.path)
-            .args(iter)
-            .status()
-            .context("Failed to execute command")?;
+        let mut process = Command::new(cmd);
+        process.current_dir(&work_dir.path).args(iter);
+
+        if self.capture_output {
+            let Output { status, stderr, .. } = process
+                .stdin(Stdio::null())
+                .output()
+                .context("Failed to execute command")?;
+            if !status.success() {
+                let stderr = String::from_utf8_lossy(&stderr);
+                match stderr.lines().last() {
+                    Some(line) => bail!("Command failed with {}: {}", status, line),
+                    None => bail!("Command failed with {}", status),
+                }
+            }
+        } else {
+            // Command output goes to stderr, keeping stdout for machine-readable reports.
+            let status = process
+                .stdout(io::stderr())
+                .status()
+                .context("Failed to execute command")?;
+            if !status.success() {
+                bail!("Command failed with {}", status);
+            }
+        }
         Ok(())
     }
 }
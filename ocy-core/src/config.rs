@@ -0,0 +1,499 @@
+use eyre::{bail, eyre, Context, Result};
+use glob::Pattern;
+use toml::{Table, Value};
+
+use regex::Regex;
+
+use crate::{
+    marker::{Content, Marker},
+    matcher::{CleanStrategy, Matcher, RemovalPattern},
+};
+
+/// Rules declared in a TOML file.
+///
+/// ```toml
+/// disable = ["XCode"]
+///
+/// [[rule]]
+/// name = "Bazel"
+/// marker = "WORKSPACE"
+/// remove = "bazel-*"
+///
+/// [[rule]]
+/// name = "CMake"
+/// marker = "CMakeLists.txt"
+/// command = "cmake --build build --target clean"
+///
+/// [[rule]]
+/// name = "Flutter Android"
+/// marker = "pubspec.yaml"
+/// remove = "android/app/build"
+///
+/// [[rule]]
+/// name = "CMake build"
+/// marker = { all = ["CMakeCache.txt", { not = ".keep-build" }] }
+/// remove = "build"
+///
+/// [[rule]]
+/// name = "Ignored"
+/// marker = "*"
+/// ignored = true
+/// exclude = ["local-data"]
+/// ```
+///
+/// A `marker` is a pattern an entry of the project must match, or a table
+/// combining predicates on entries, file contents and parents. A rule named
+/// like an existing rule replaces it. Rules with `ignored` remove what git
+/// ignores in work trees, save the `exclude` patterns and
+/// [`DEFAULT_IGNORED_EXCLUSIONS`](crate::matcher::DEFAULT_IGNORED_EXCLUSIONS).
+///
+/// Per-directory `.ocy.toml` files use the same format and may also list
+/// `protect` patterns, relative to their directory, for paths that must never
+/// be proposed for removal. As they come with the directories walked, maybe
+/// from an untrusted repository, their rules can only remove paths, see
+/// [`RulesConfig::parse_local`].
+pub struct RulesConfig {
+    pub rules: Vec<Matcher>,
+    pub disabled: Vec<String>,
+    pub protected: Vec<Pattern>,
+}
+
+impl RulesConfig {
+    pub fn parse(source: &str) -> Result<Self> {
+        let table: Table = source.parse().wrap_err("Invalid TOML")?;
+
+        let mut rules = Vec::new();
+        let mut disabled = Vec::new();
+        let mut protected = Vec::new();
+
+        for (key, value) in table {
+            match key.as_str() {
+                "disable" => disabled = parse_string_list(&key, value)?,
+                "protect" => {
+                    protected = parse_string_list(&key, value)?
+                        .iter()
+                        .map(|p| parse_pattern("protect", p))
+                        .collect::<Result<_>>()?;
+                }
+                "rule" => {
+                    let Value::Array(entries) = value else {
+                        bail!("`rule` must be an array of tables, use `[[rule]]`");
+                    };
+                    for (index, entry) in entries.into_iter().enumerate() {
+                        let name = entry.get("name").and_then(Value::as_str).map(str::to_owned);
+                        let rule = parse_rule(entry).wrap_err_with(|| match name {
+                            Some(name) => format!("Invalid rule #{} `{}`", index + 1, name),
+                            None => format!("Invalid rule #{}", index + 1),
+                        })?;
+                        rules.push(rule);
+                    }
+                }
+                _ => bail!("Unknown key `{}`", key),
+            }
+        }
+
+        Ok(Self {
+            rules,
+            disabled,
+            protected,
+        })
+    }
+
+    /// Parses a per-directory `.ocy.toml`, rejecting rules running commands
+    /// or removing what git ignores: they would run, resp. remove more than
+    /// the file says, on behalf of whoever wrote it.
+    pub fn parse_local(source: &str) -> Result<Self> {
+        let config = Self::parse(source)?;
+        for (index, rule) in config.rules.iter().enumerate() {
+            let key = match rule.clean_strategy {
+                CleanStrategy::Remove(_) => continue,
+                CleanStrategy::RunCommand(_) => "command",
+                CleanStrategy::RemoveIgnored(_) => "ignored",
+            };
+            bail!(
+                "Invalid rule #{} `{}`: `{}` is only allowed in the rule file, not in `.ocy.toml` files",
+                index + 1,
+                rule.name,
+                key
+            );
+        }
+        Ok(config)
+    }
+
+    /// Applies this configuration on top of `matchers`: disabled and
+    /// overridden rules are removed, then the declared rules are appended.
+    pub fn apply(self, mut matchers: Vec<Matcher>) -> Vec<Matcher> {
+        matchers.retain(|m| self.keeps(&m.name));
+        matchers.extend(self.rules);
+        matchers
+    }
+
+    /// Whether an existing rule named `name` survives this configuration.
+    pub fn keeps(&self, name: &str) -> bool {
+        !self.disabled.iter().any(|d| d == name) && !self.rules.iter().any(|r| &*r.name == name)
+    }
+}
+
+fn parse_rule(value: Value) -> Result<Matcher> {
+    let Value::Table(mut table) = value else {
+        bail!("expected a table");
+    };
+
+    let name = take_string(&mut table, "name")?.ok_or_else(|| eyre!("missing `name`"))?;
+    let marker = table
+        .remove("marker")
+        .ok_or_else(|| eyre!("missing `marker`"))?;
+    let marker = parse_marker(marker)?;
+    let remove = take_string(&mut table, "remove")?;
+    let command = take_string(&mut table, "command")?;
+    let ignored = take_bool(&mut table, "ignored")?.unwrap_or(false);
+    let exclude = table
+        .remove("exclude")
+        .map(|value| parse_string_list("exclude", value))
+        .transpose()?;
+
+    if let Some(key) = table.keys().next() {
+        bail!("unknown key `{}`", key);
+    }
+    if exclude.is_some() && !ignored {
+        bail!("`exclude` only applies to rules with `ignored = true`");
+    }
+
+    match (remove, command, ignored) {
+        (Some(remove), None, false) => Ok(Matcher::with_removal_pattern(
+            name.into(),
+            marker,
+            RemovalPattern::parse(&remove)
+                .wrap_err_with(|| format!("invalid `remove` pattern `{}`", remove))?,
+        )),
+        (None, Some(command), false) => {
+            if command.trim().is_empty() {
+                bail!("`command` cannot be empty");
+            }
+            Ok(Matcher::with_command_strategy(name.into(), marker, command))
+        }
+        (None, None, true) => {
+            let excluded = exclude
+                .unwrap_or_default()
+                .iter()
+                .map(|p| parse_pattern("exclude", p))
+                .collect::<Result<_>>()?;
+            Ok(Matcher::with_ignored_strategy(
+                name.into(),
+                marker,
+                excluded,
+            ))
+        }
+        (None, None, false) => bail!("one of `remove`, `command` or `ignored` is required"),
+        _ => bail!("`remove`, `command` and `ignored` are mutually exclusive"),
+    }
+}
+
+/// Parses a marker, either a pattern matching an entry or a table with one
+/// of these keys:
+///
+/// * `entry`, `file` or `dir`: a pattern matching an entry, a file or a
+///   directory. `file` may add `contains`, a text the file contains, or
+///   `matches`, a regular expression its content matches.
+/// * `name`: a pattern matching the name of the directory itself.
+/// * `parent`: a marker of the parent directory.
+/// * `all`, `any`: an array of markers that must all, resp. any, match.
+/// * `not`: a marker that must not match.
+fn parse_marker(value: Value) -> Result<Marker> {
+    let mut table = match value {
+        Value::String(pattern) => return Ok(Marker::Entry(parse_pattern("marker", &pattern)?)),
+        Value::Table(table) => table,
+        other => bail!(
+            "`marker` must be a string or a table, found {}",
+            other.type_str()
+        ),
+    };
+
+    let contains = take_string(&mut table, "contains")?;
+    let matches = take_string(&mut table, "matches")?;
+    let content = match (contains, matches) {
+        (Some(text), None) => Some(Content::Contains(text)),
+        (None, Some(regex)) => Some(Content::Matches(
+            Regex::new(&regex).wrap_err_with(|| format!("invalid `matches` regex `{}`", regex))?,
+        )),
+        (None, None) => None,
+        (Some(_), Some(_)) => bail!("`contains` and `matches` are mutually exclusive"),
+    };
+
+    let mut entries = table.into_iter();
+    let (Some((key, value)), None) = (entries.next(), entries.next()) else {
+        bail!("a marker table needs exactly one of `entry`, `file`, `dir`, `name`, `parent`, `all`, `any` or `not`");
+    };
+    if content.is_some() && key != "file" {
+        bail!("`contains` and `matches` only apply to `file` markers");
+    }
+    let pattern = |value: Value| match value {
+        Value::String(pattern) => parse_pattern(&key, &pattern),
+        other => bail!("`{}` must be a string, found {}", key, other.type_str()),
+    };
+    let markers = |value: Value| match value {
+        Value::Array(values) => values.into_iter().map(parse_marker).collect(),
+        other => bail!(
+            "`{}` must be an array of markers, found {}",
+            key,
+            other.type_str()
+        ),
+    };
+
+    Ok(match key.as_str() {
+        "entry" => Marker::Entry(pattern(value)?),
+        "file" => Marker::File(pattern(value)?, content),
+        "dir" => Marker::Directory(pattern(value)?),
+        "name" => Marker::Name(pattern(value)?),
+        "parent" => Marker::Parent(Box::new(parse_marker(value)?)),
+        "all" => Marker::All(markers(value)?),
+        "any" => Marker::Any(markers(value)?),
+        "not" => Marker::Not(Box::new(parse_marker(value)?)),
+        _ => bail!("unknown marker `{}`", key),
+    })
+}
+
+fn take_string(table: &mut Table, key: &str) -> Result<Option<String>> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(other) => bail!("`{}` must be a string, found {}", key, other.type_str()),
+    }
+}
+
+fn take_bool(table: &mut Table, key: &str) -> Result<Option<bool>> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::Boolean(b)) => Ok(Some(b)),
+        Some(other) => bail!("`{}` must be a boolean, found {}", key, other.type_str()),
+    }
+}
+
+fn parse_string_list(key: &str, value: Value) -> Result<Vec<String>> {
+    let Value::Array(values) = value else {
+        bail!("`{}` must be an array of strings", key);
+    };
+    values
+        .into_iter()
+        .map(|v| match v {
+            Value::String(s) => Ok(s),
+            other => bail!(
+                "`{}` must only contain strings, found {}",
+                key,
+                other.type_str()
+            ),
+        })
+        .collect()
+}
+
+fn parse_pattern(key: &str, pattern: &str) -> Result<Pattern> {
+    Pattern::new(pattern).wrap_err_with(|| format!("invalid `{}` pattern `{}`", key, pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use glob::Pattern;
+
+    use crate::{
+        marker::{Content, Marker},
+        matcher::{CleanStrategy, Matcher},
+        models::{FileInfo, SimpleFileKind},
+    };
+
+    use super::RulesConfig;
+
+    fn builtin() -> Vec<Matcher> {
+        vec![
+            Matcher::with_remove_strategy(
+                "Cargo".into(),
+                Pattern::new("Cargo.toml").unwrap(),
+                Pattern::new("target").unwrap(),
+            )
+            .unwrap(),
+            Matcher::with_remove_strategy(
+                "XCode".into(),
+                Pattern::new("*").unwrap(),
+                Pattern::new("DerivedData").unwrap(),
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn rules_are_added_disabled_and_overridden() -> eyre::Result<()> {
+        let config = RulesConfig::parse(
+            r#"
+            disable = ["XCode"]
+
+            [[rule]]
+            name = "Bazel"
+            marker = "WORKSPACE"
+            remove = "bazel-*"
+
+            [[rule]]
+            name = "Cargo"
+            marker = "Cargo.toml"
+            command = "cargo clean"
+            "#,
+        )?;
+
+        let matchers = config.apply(builtin());
+        let names: Vec<_> = matchers.iter().map(|m| m.name.as_ref()).collect();
+        assert_eq!(names, ["Bazel", "Cargo"]);
+        assert!(matches!(
+            &matchers[1].clean_strategy,
+            CleanStrategy::RunCommand(cmd) if cmd.as_ref() == "cargo clean"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ignored_rules_take_exclusions() -> eyre::Result<()> {
+        let config = RulesConfig::parse(
+            r#"
+            [[rule]]
+            name = "Ignored"
+            marker = "*"
+            ignored = true
+            exclude = ["local-*"]
+            "#,
+        )?;
+
+        let CleanStrategy::RemoveIgnored(exclusions) = &config.rules[0].clean_strategy else {
+            panic!("expected a rule removing ignored directories");
+        };
+        let entry = |name: &str| FileInfo::new(name.into(), name.into(), SimpleFileKind::Directory);
+        assert!(exclusions.excludes(&entry("local-data")));
+        assert!(exclusions.excludes(&entry(".env.local")));
+        assert!(!exclusions.excludes(&entry("dist")));
+
+        let err = RulesConfig::parse(
+            r#"
+            [[rule]]
+            name = "Both"
+            marker = "*"
+            ignored = true
+            remove = "dist"
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert!(format!("{:#}", err).contains("mutually exclusive"));
+        Ok(())
+    }
+
+    #[test]
+    fn markers_combine_predicates() -> eyre::Result<()> {
+        let config = RulesConfig::parse(
+            r#"
+            [[rule]]
+            name = "CMake build"
+            marker = { all = ["CMakeCache.txt", { not = ".keep-build" }] }
+            remove = "build"
+
+            [[rule]]
+            name = "Cargo package"
+            marker = { any = [{ file = "Cargo.toml", contains = "[package]" }, { parent = { dir = "crates" } }] }
+            remove = "target"
+            "#,
+        )?;
+
+        assert!(matches!(
+            &config.rules[0].to_match,
+            Marker::All(markers) if matches!(markers[..], [Marker::Entry(_), Marker::Not(_)])
+        ));
+        assert!(matches!(
+            &config.rules[1].to_match,
+            Marker::Any(markers)
+                if matches!(markers[..], [Marker::File(_, Some(Content::Contains(_))), Marker::Parent(_)])
+        ));
+
+        for (marker, error) in [
+            (r#"{ dir = "a", file = "b" }"#, "exactly one of"),
+            (r#"{ dir = "a", contains = "b" }"#, "only apply to `file`"),
+            (
+                r#"{ file = "a", matches = "(" }"#,
+                "invalid `matches` regex",
+            ),
+            (r#"{ all = "a" }"#, "must be an array of markers"),
+            (r#"{ nope = "a" }"#, "unknown marker `nope`"),
+        ] {
+            let source = format!(
+                "[[rule]]\nname = \"R\"\nmarker = {}\nremove = \"out\"",
+                marker
+            );
+            let err = RulesConfig::parse(&source).err().unwrap();
+            let message = format!("{:#}", err);
+            assert!(message.contains(error), "{}", message);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_removal_patterns_are_rejected() {
+        for (remove, error) in [("a/[b/c]", "invalid component `[b`"), ("/", "no path")] {
+            let source = format!(
+                "[[rule]]\nname = \"R\"\nmarker = \"*\"\nremove = \"{}\"",
+                remove
+            );
+            let err = RulesConfig::parse(&source).err().unwrap();
+            let message = format!("{:#}", err);
+            assert!(message.contains("invalid `remove` pattern"), "{}", message);
+            assert!(message.contains(error), "{}", message);
+        }
+    }
+
+    #[test]
+    fn local_rules_can_only_remove_paths() -> eyre::Result<()> {
+        let local = RulesConfig::parse_local(
+            r#"
+            protect = ["fixtures"]
+
+            [[rule]]
+            name = "Bazel"
+            marker = "WORKSPACE"
+            remove = "bazel-*"
+            "#,
+        )?;
+        assert_eq!(local.rules.len(), 1);
+
+        for (rule, key) in [
+            ("command = \"sh ./payload.sh\"", "command"),
+            ("ignored = true", "ignored"),
+        ] {
+            let source = format!("[[rule]]\nname = \"Sneaky\"\nmarker = \"*\"\n{}", rule);
+            assert!(RulesConfig::parse(&source).is_ok());
+            let message = format!("{:#}", RulesConfig::parse_local(&source).err().unwrap());
+            assert!(message.contains("rule #1 `Sneaky`"), "{}", message);
+            assert!(
+                message.contains(&format!("`{}` is only allowed", key)),
+                "{}",
+                message
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_rule_is_reported_with_its_name() {
+        let err = RulesConfig::parse(
+            r#"
+            [[rule]]
+            name = "Good"
+            marker = "a"
+            remove = "b"
+
+            [[rule]]
+            name = "Broken"
+            marker = "[oops"
+            remove = "out"
+            "#,
+        )
+        .err()
+        .unwrap();
+
+        let message = format!("{:#}", err);
+        assert!(message.contains("Invalid rule #2 `Broken`"), "{}", message);
+        assert!(message.contains("`marker` pattern `[oops`"), "{}", message);
+    }
+}
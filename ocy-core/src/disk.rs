@@ -0,0 +1,118 @@
+use std::{ffi::CString, io, mem::MaybeUninit, os::unix::ffi::OsStrExt, path::Path};
+
+use eyre::{Context, Result};
+
+#[cfg(target_os = "linux")]
+use crate::filesystem::FileSystemKind;
+
+/// Space of the file system containing a path, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub total: u64,
+    /// Space available to unprivileged users.
+    pub available: u64,
+}
+
+impl DiskUsage {
+    pub fn of(path: &Path) -> Result<Self> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: `c_path` is NUL-terminated and `stat` is only read when
+        // statvfs succeeds, which initializes it.
+        let stat = unsafe {
+            if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error())
+                    .wrap_err_with(|| format!("Cannot stat file system of {}", path.display()));
+            }
+            stat.assume_init()
+        };
+        let fragment_size = stat.f_frsize as u64;
+        Ok(Self {
+            total: stat.f_blocks as u64 * fragment_size,
+            available: stat.f_bavail as u64 * fragment_size,
+        })
+    }
+}
+
+/// Kind of the file system containing `path`, from the magic number
+/// reported by statfs. Unknown file systems are taken for local ones.
+#[cfg(target_os = "linux")]
+pub(crate) fn file_system_kind(path: &Path) -> FileSystemKind {
+    // From linux/magic.h
+    const PSEUDO: &[u32] = &[
+        0x9fa0,     // proc
+        0x62656572, // sysfs
+        0x1cd1,     // devpts
+        0x27e0eb,   // cgroup
+        0x63677270, // cgroup2
+        0x64626720, // debugfs
+        0x74726163, // tracefs
+        0x73636673, // securityfs
+        0xcafe4a11, // bpf
+        0x6165676c, // pstore
+        0xde5e81e4, // efivarfs
+        0x62656570, // configfs
+        0x42494e4d, // binfmt_misc
+        0x65735543, // fusectl
+        0x19800202, // mqueue
+    ];
+    const NETWORK: &[u32] = &[
+        0x6969,     // nfs
+        0x517b,     // smb
+        0xff534d42, // cifs
+        0xfe534d42, // smb2
+        0x5346414f, // afs
+        0x00c36400, // ceph
+        0x73757245, // coda
+        0x564c,     // ncp
+    ];
+
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return FileSystemKind::Local;
+    };
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: as in `DiskUsage::of`.
+    let stat = unsafe {
+        if libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return FileSystemKind::Local;
+        }
+        stat.assume_init()
+    };
+    // Magic numbers are 32 bits, whatever the width of f_type.
+    let magic = stat.f_type as u32;
+    if PSEUDO.contains(&magic) {
+        FileSystemKind::Pseudo
+    } else if NETWORK.contains(&magic) {
+        FileSystemKind::Network
+    } else {
+        FileSystemKind::Local
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::DiskUsage;
+
+    #[test]
+    fn reports_the_space_of_the_root_file_system() -> eyre::Result<()> {
+        let usage = DiskUsage::of(Path::new("/"))?;
+
+        assert!(usage.total > 0);
+        assert!(usage.available <= usage.total);
+        assert!(DiskUsage::of(Path::new("/does/not/exist")).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn recognizes_pseudo_file_systems() {
+        use super::file_system_kind;
+        use crate::filesystem::FileSystemKind;
+
+        assert_eq!(file_system_kind(Path::new("/proc")), FileSystemKind::Pseudo);
+        assert_eq!(file_system_kind(Path::new("/tmp")), FileSystemKind::Local);
+    }
+}
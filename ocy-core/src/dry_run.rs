@@ -0,0 +1,121 @@
+use std::sync::Mutex;
+
+use eyre::Result;
+
+use crate::{command::CommandExecutor, filesystem::FileSystemClean, models::FileInfo};
+
+/// An action the cleaner would have performed.
+#[derive(Debug, Clone)]
+pub enum PlannedAction {
+    Remove(FileInfo),
+    RunCommand { work_dir: FileInfo, command: String },
+}
+
+/// Stands in for both the file system and the command executor of a
+/// `Cleaner`, recording the actions it is asked to perform instead of
+/// touching the disk.
+#[derive(Debug, Default)]
+pub struct DryRun {
+    actions: Mutex<Vec<PlannedAction>>,
+    moves_files: bool,
+}
+
+impl DryRun {
+    /// Plans to move files out of the way, as the trash or the quarantine
+    /// do, rather than to delete them.
+    pub fn with_moves_files(self, moves_files: bool) -> Self {
+        Self {
+            moves_files,
+            ..self
+        }
+    }
+
+    pub fn into_actions(self) -> Vec<PlannedAction> {
+        self.actions.into_inner().unwrap()
+    }
+}
+
+impl FileSystemClean for &DryRun {
+    fn remove_file(&self, file: &FileInfo) -> Result<()> {
+        self.actions
+            .lock()
+            .unwrap()
+            .push(PlannedAction::Remove(file.clone()));
+        Ok(())
+    }
+
+    fn moves_files(&self) -> bool {
+        self.moves_files
+    }
+}
+
+impl CommandExecutor for &DryRun {
+    fn execute_command(&self, work_dir: &FileInfo, command: &str) -> Result<()> {
+        self.actions
+            .lock()
+            .unwrap()
+            .push(PlannedAction::RunCommand {
+                work_dir: work_dir.clone(),
+                command: command.to_string(),
+            });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use eyre::Report;
+
+    use crate::{
+        cleaner::{CleanSummary, Cleaner, CleanerNotifier},
+        models::{FileInfo, RemovalCandidate, SimpleFileKind},
+    };
+
+    use super::{DryRun, PlannedAction};
+
+    #[derive(Default)]
+    struct CountingNotifier {
+        succeeded: AtomicUsize,
+    }
+
+    impl CleanerNotifier for &CountingNotifier {
+        fn notify_removal_started(&self, _candidate: &RemovalCandidate) {}
+
+        fn notify_removal_success(&self, _candidate: RemovalCandidate, _freed: Option<u64>) {
+            self.succeeded.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn notify_removal_failed(&self, _candidate: RemovalCandidate, _report: Report) {}
+
+        fn notify_removal_finish(&self, _summary: &CleanSummary) {}
+    }
+
+    #[test]
+    fn records_planned_actions_in_order() {
+        let dir = |path: &str| {
+            FileInfo::new(
+                path.into(),
+                path.rsplit('/').next().unwrap().into(),
+                SimpleFileKind::Directory,
+            )
+        };
+        let candidates = vec![
+            RemovalCandidate::new("Cargo".into(), dir("/p/target"), Some(42)),
+            RemovalCandidate::new_cmd("Make".into(), dir("/q"), "make clean".into()),
+        ];
+        let dry_run = DryRun::default();
+        let notifier = CountingNotifier::default();
+
+        Cleaner::new(candidates, &dry_run, &dry_run, &notifier).clean();
+
+        assert_eq!(notifier.succeeded.load(Ordering::SeqCst), 2);
+        let actions = dry_run.into_actions();
+        assert!(matches!(&actions[0], PlannedAction::Remove(f) if f.name == "target"));
+        assert!(matches!(
+            &actions[1],
+            PlannedAction::RunCommand { work_dir, command } if work_dir.name == "q" && command == "make clean"
+        ));
+    }
+}
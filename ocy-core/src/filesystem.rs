@@ -2,34 +2,111 @@ use eyre::Context;
 use eyre::Result;
 
 use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    env,
     fs::{self, DirEntry},
-    path::Path,
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
 
+#[cfg(unix)]
+use crate::disk::DiskUsage;
 use crate::models::FileInfo;
+use crate::models::RemovalCandidate;
 use crate::models::SimpleFileKind;
+use crate::work_queue;
 
-pub trait FileSystem {
+/// Shared between threads when walking in parallel.
+pub trait FileSystem: Sync {
     fn current_directory(&self) -> Result<FileInfo>;
 
     fn list_files(&self, file: &FileInfo) -> Result<Vec<FileInfo>>;
 
     fn file_size(&self, file: &FileInfo) -> Result<u64>;
+
+    fn read_to_string(&self, file: &FileInfo) -> Result<String>;
+
+    fn read(&self, file: &FileInfo) -> Result<Vec<u8>>;
+
+    /// What the symbolic link `file` points to, under the path and name of
+    /// the link.
+    fn follow_symlink(&self, file: &FileInfo) -> Result<FileInfo>;
+
+    /// Identifies `file`, following symbolic links, to tell when two paths
+    /// lead to the same directory.
+    fn file_id(&self, file: &FileInfo) -> Option<(u64, u64)>;
+
+    /// Kind of the file system `dir` is on, checked when the walk enters a
+    /// new one.
+    fn file_system_kind(&self, dir: &FileInfo) -> FileSystemKind;
 }
 
-pub trait FileSystemClean {
+/// Shared between threads when cleaning concurrently.
+pub trait FileSystemClean: Sync {
     fn remove_file(&self, file: &FileInfo) -> Result<()>;
+
+    /// Removes `file` on behalf of `candidate`. Implementations keeping a
+    /// record of what they remove override this to access the candidate.
+    fn remove_candidate(&self, _candidate: &RemovalCandidate, file: &FileInfo) -> Result<()> {
+        self.remove_file(file)
+    }
+
+    /// Current size of `file`, used to measure what commands free.
+    fn measure_size(&self, _file: &FileInfo) -> Option<u64> {
+        None
+    }
+
+    /// Space available on the file system holding `path`.
+    fn available_space(&self, _path: &Path) -> Option<u64> {
+        None
+    }
+
+    /// Whether removed files are moved out of the way rather than deleted,
+    /// which frees no space.
+    fn moves_files(&self) -> bool {
+        false
+    }
+}
+/// How the size of files is computed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SizeMode {
+    /// Sum of file lengths, like `du --apparent-size`.
+    #[default]
+    Apparent,
+    /// Allocated blocks, counting hard-linked files once, like `du`. Falls
+    /// back to apparent sizes where blocks are not reported.
+    DiskUsage,
+}
+
+/// What a file system holds, as far as walking it is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileSystemKind {
+    Local,
+    /// Kernel interfaces like `/proc` or `/sys`, never worth walking.
+    Pseudo,
+    /// Shares like NFS or SMB, slow to walk and possibly used by others.
+    Network,
+}
+
+#[derive(Debug, Default)]
+pub struct RealFileSystem {
+    size_mode: SizeMode,
+    /// Leave out what is mounted inside the files sized.
+    one_file_system: bool,
+    /// Threads removing a directory, `0` or `1` to remove it sequentially.
+    jobs: usize,
+    /// `(device, inode)` of the hard-linked files already counted by
+    /// `file_size`, so that files shared between candidates count once.
+    seen: Mutex<HashSet<(u64, u64)>>,
 }
-pub struct RealFileSystem;
 
 impl FileSystem for RealFileSystem {
     fn current_directory(&self) -> Result<FileInfo> {
         let path_buf = std::env::current_dir()?;
-        Ok(FileInfo::new(
-            path_buf,
-            "".into(),
-            SimpleFileKind::Directory,
-        ))
+        let device = fs::metadata(&path_buf).ok().and_then(|m| device_of(&m));
+        Ok(FileInfo::new(path_buf, "".into(), SimpleFileKind::Directory).with_device(device))
     }
 
     fn list_files(&self, file: &FileInfo) -> Result<Vec<FileInfo>> {
@@ -42,31 +119,153 @@ impl FileSystem for RealFileSystem {
     }
 
     fn file_size(&self, file: &FileInfo) -> Result<u64> {
-        RealFileSystem::get_size(&file.path)
+        let device = self.device_boundary(file);
+        match self.size_mode {
+            SizeMode::Apparent => apparent_size(&file.path, device),
+            SizeMode::DiskUsage => disk_usage(&file.path, &self.seen, device),
+        }
+    }
+
+    fn read_to_string(&self, file: &FileInfo) -> Result<String> {
+        Ok(fs::read_to_string(&file.path)?)
+    }
+
+    fn read(&self, file: &FileInfo) -> Result<Vec<u8>> {
+        Ok(fs::read(&file.path)?)
+    }
+
+    fn follow_symlink(&self, file: &FileInfo) -> Result<FileInfo> {
+        let metadata = fs::metadata(&file.path)?;
+        Ok(FileInfo::new(
+            file.path.clone(),
+            file.name.clone(),
+            kind_of(metadata.file_type()),
+        )
+        .with_modified(metadata.modified().ok())
+        .with_device(device_of(&metadata)))
+    }
+
+    fn file_id(&self, file: &FileInfo) -> Option<(u64, u64)> {
+        file_id(&file.path)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn file_system_kind(&self, dir: &FileInfo) -> FileSystemKind {
+        crate::disk::file_system_kind(&dir.path)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn file_system_kind(&self, _dir: &FileInfo) -> FileSystemKind {
+        FileSystemKind::Local
     }
 }
 
 impl RealFileSystem {
+    pub fn new(size_mode: SizeMode) -> Self {
+        Self {
+            size_mode,
+            one_file_system: false,
+            jobs: 1,
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Sizes files without what other file systems mount inside them.
+    pub fn with_one_file_system(self, one_file_system: bool) -> Self {
+        Self {
+            one_file_system,
+            ..self
+        }
+    }
+
+    /// Removes directories on `jobs` threads, which pays off on large trees
+    /// where removal waits on the disk rather than on the CPU.
+    pub fn with_jobs(self, jobs: usize) -> Self {
+        Self { jobs, ..self }
+    }
+
+    /// Sum of the lengths of the files under `path`. Symbolic links count
+    /// for their own length, what they point to is not freed by removing them.
     pub fn get_size<P>(path: P) -> Result<u64>
     where
         P: AsRef<Path>,
     {
-        let mut result = 0;
-
-        if path.as_ref().is_dir() {
-            for entry in fs::read_dir(&path)? {
-                let current_path = entry?.path();
-                if current_path.is_file() {
-                    result += current_path.metadata()?.len();
-                } else {
-                    result += RealFileSystem::get_size(current_path)?;
-                }
-            }
-        } else {
-            result = path.as_ref().metadata()?.len();
+        apparent_size(path.as_ref(), None)
+    }
+
+    /// Device sizing `file` must not leave.
+    fn device_boundary(&self, file: &FileInfo) -> Option<u64> {
+        file.device.filter(|_| self.one_file_system)
+    }
+}
+
+/// Apparent size of `path` and its content, leaving out what is on another
+/// device than `device` when given.
+fn apparent_size(path: &Path, device: Option<u64>) -> Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if device.is_some() && device_of(&metadata) != device {
+        return Ok(0);
+    }
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut result = 0;
+    for entry in fs::read_dir(path)? {
+        result += apparent_size(&entry?.path(), device)?;
+    }
+    Ok(result)
+}
+
+/// Space allocated to `path` and its content, skipping hard-linked files
+/// already in `seen`, and what is on another device than `device` when given.
+/// Symbolic links are not followed. `seen` is only locked for hard-linked
+/// files, so that candidates are sized concurrently.
+#[cfg(unix)]
+fn disk_usage(path: &Path, seen: &Mutex<HashSet<(u64, u64)>>, device: Option<u64>) -> Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks is in 512-byte units whatever the block size of the file system
+    const BLOCK_SIZE: u64 = 512;
+
+    let metadata = fs::symlink_metadata(path)?;
+    if device.is_some_and(|device| device != metadata.dev()) {
+        return Ok(0);
+    }
+    if !metadata.is_dir()
+        && metadata.nlink() > 1
+        && !seen
+            .lock()
+            .unwrap()
+            .insert((metadata.dev(), metadata.ino()))
+    {
+        return Ok(0);
+    }
+
+    let mut result = metadata.blocks() * BLOCK_SIZE;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            result += disk_usage(&entry?.path(), seen, device)?;
         }
-        Ok(result)
     }
+    Ok(result)
+}
+
+#[cfg(not(unix))]
+fn disk_usage(path: &Path, _seen: &Mutex<HashSet<(u64, u64)>>, device: Option<u64>) -> Result<u64> {
+    apparent_size(path, device)
+}
+
+#[cfg(unix)]
+fn device_of(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_metadata: &fs::Metadata) -> Option<u64> {
+    None
 }
 
 fn map_entry_to_simple_file(entry: &DirEntry) -> Result<FileInfo> {
@@ -77,34 +276,265 @@ fn map_entry_to_simple_file(entry: &DirEntry) -> Result<FileInfo> {
         .into_string()
         .map_err(|_| eyre::eyre!("Cannot convert os string"))?;
 
-    let file_type = entry.file_type()?;
+    let kind = kind_of(entry.file_type()?);
+    let metadata = entry.metadata().ok();
+    let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+    let device = metadata.as_ref().and_then(device_of);
+
+    Ok(FileInfo::new(path, name, kind)
+        .with_modified(modified)
+        .with_device(device))
+}
 
-    let kind = if file_type.is_dir() {
+fn kind_of(file_type: fs::FileType) -> SimpleFileKind {
+    if file_type.is_dir() {
         SimpleFileKind::Directory
+    } else if file_type.is_symlink() {
+        SimpleFileKind::Symlink
     } else {
         SimpleFileKind::File
-    };
+    }
+}
 
-    Ok(FileInfo::new(path, name, kind))
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Without inodes, the canonical path stands for the file.
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    fs::canonicalize(path).ok()?.hash(&mut hasher);
+    Some((0, hasher.finish()))
 }
 
 impl FileSystemClean for RealFileSystem {
     fn remove_file(&self, file: &FileInfo) -> Result<()> {
-        if file.kind == SimpleFileKind::Directory {
+        if file.kind == SimpleFileKind::Directory && self.jobs > 1 {
+            remove_dir_parallel(&file.path, self.jobs)?;
+        } else if file.kind == SimpleFileKind::Directory {
             std::fs::remove_dir_all(&file.path)?;
         } else {
             std::fs::remove_file(&file.path)?;
         }
         Ok(())
     }
+
+    fn measure_size(&self, file: &FileInfo) -> Option<u64> {
+        // Before and after sizes of a command must not share counted files.
+        let device = self.device_boundary(file);
+        match self.size_mode {
+            SizeMode::Apparent => apparent_size(&file.path, device).ok(),
+            SizeMode::DiskUsage => disk_usage(&file.path, &Mutex::default(), device).ok(),
+        }
+    }
+
+    fn available_space(&self, path: &Path) -> Option<u64> {
+        available_space(path)
+    }
 }
 
-pub struct MockFileSystemClean;
+/// Removes the files under `path` on `threads` threads, then the directories
+/// once empty, deepest first. Symbolic links are removed, not followed.
+fn remove_dir_parallel(path: &Path, threads: usize) -> Result<()> {
+    let emptied = Mutex::new(Vec::new());
+    let error = Mutex::new(None);
+    work_queue::run(
+        threads,
+        vec![path.to_path_buf()],
+        |dir: PathBuf, subdirs| {
+            if error.lock().unwrap().is_some() {
+                return;
+            }
+            match remove_files_in(&dir, subdirs) {
+                Ok(()) => emptied.lock().unwrap().push(dir),
+                Err(e) => {
+                    error.lock().unwrap().get_or_insert(e);
+                }
+            }
+        },
+    );
+    if let Some(e) = error.into_inner().unwrap() {
+        return Err(e.into());
+    }
+
+    let mut emptied = emptied.into_inner().unwrap();
+    emptied.sort_by_key(|dir| Reverse(dir.components().count()));
+    for dir in emptied {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Removes the files of `dir`, adding its subdirectories to `subdirs`.
+fn remove_files_in(dir: &Path, subdirs: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            subdirs.push(entry.path());
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn available_space(path: &Path) -> Option<u64> {
+    DiskUsage::of(path).ok().map(|usage| usage.available)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `~/.local/share`.
+pub(crate) fn data_home() -> Result<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => {
+            let home = env::var_os("HOME").ok_or_else(|| eyre::eyre!("HOME is not set"))?;
+            Ok(PathBuf::from(home).join(".local").join("share"))
+        }
+    }
+}
+
+/// Moves `from` to `to`, falling back to copying then removing the original
+/// when they are on different file systems.
+pub(crate) fn move_path(from: &Path, to: &Path) -> Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_path(from, to) {
+                let _ = remove_path(to);
+                return Err(e).wrap_err("Failed to copy across file systems");
+            }
+            remove_path(from)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())?;
+    } else if file_type.is_symlink() {
+        copy_symlink(from, to)?;
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+
+    use crate::models::{FileInfo, SimpleFileKind};
+
+    use super::{FileSystem, FileSystemClean, RealFileSystem, SizeMode};
+
+    #[test]
+    fn disk_usage_counts_hard_links_once() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let project = |name: &str| -> eyre::Result<FileInfo> {
+            let path = dir.path().join(name);
+            fs::create_dir(&path)?;
+            Ok(FileInfo::new(path, name.into(), SimpleFileKind::Directory))
+        };
+        let first = project("first")?;
+        let second = project("second")?;
+        fs::write(first.path.join("data"), vec![1; 64 * 1024])?;
+        fs::hard_link(first.path.join("data"), first.path.join("link"))?;
+        fs::hard_link(first.path.join("data"), second.path.join("link"))?;
+        // Sparse: a large length with almost no allocated block
+        fs::File::create(second.path.join("sparse"))?.set_len(1024 * 1024 * 1024)?;
+
+        let apparent = RealFileSystem::new(SizeMode::Apparent);
+        assert_eq!(apparent.file_size(&first)?, 2 * 64 * 1024);
+
+        let disk = RealFileSystem::new(SizeMode::DiskUsage);
+        let first_size = disk.file_size(&first)?;
+        let second_size = disk.file_size(&second)?;
+        assert!((64 * 1024..2 * 64 * 1024).contains(&first_size));
+        assert!(second_size < 64 * 1024);
+
+        Ok(())
+    }
+
+    #[test]
+    fn removes_directories_in_parallel_without_following_links() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside)?;
+        fs::write(outside.join("kept"), "kept")?;
+
+        let target = dir.path().join("target");
+        for i in 0..8 {
+            let nested = target.join(format!("dir{}", i)).join("nested");
+            fs::create_dir_all(&nested)?;
+            fs::write(nested.join("file"), "data")?;
+        }
+        std::os::unix::fs::symlink(&outside, target.join("link"))?;
+
+        let target = FileInfo::new(target, "target".into(), SimpleFileKind::Directory);
+        RealFileSystem::default()
+            .with_jobs(4)
+            .remove_file(&target)?;
+
+        assert!(!target.path.exists());
+        assert!(outside.join("kept").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sizes_do_not_follow_symlinks() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("big"), vec![1; 64 * 1024])?;
+        let target = dir.path().join("target");
+        fs::create_dir(&target)?;
+        fs::write(target.join("small"), "data")?;
+        std::os::unix::fs::symlink(dir.path(), target.join("up"))?;
+        std::os::unix::fs::symlink(dir.path().join("big"), target.join("big"))?;
+
+        let link_lengths = fs::symlink_metadata(target.join("up"))?.len()
+            + fs::symlink_metadata(target.join("big"))?.len();
+        assert_eq!(RealFileSystem::get_size(&target)?, 4 + link_lengths);
 
-impl FileSystemClean for MockFileSystemClean {
-    fn remove_file(&self, _file: &FileInfo) -> Result<()> {
-        std::thread::sleep(std::time::Duration::from_secs(1));
         Ok(())
-        //        Err(eyre::eyre!("Failed"))
     }
 }
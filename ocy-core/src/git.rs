@@ -0,0 +1,289 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use eyre::{bail, eyre, Result};
+
+use crate::{
+    filesystem::FileSystem,
+    gitignore::IgnoreRules,
+    models::{FileInfo, SimpleFileKind},
+};
+
+/// Name of the entry marking the root of a git work tree.
+pub const GIT_DIR: &str = ".git";
+
+/// Flag of index entries followed by a second flags field, from version 3.
+const EXTENDED_FLAG: u16 = 0x4000;
+
+/// Paths tracked in the index of a git work tree, read straight from
+/// `.git/index` without the `git` binary.
+#[derive(Debug)]
+pub struct GitIndex {
+    work_tree: PathBuf,
+    /// Tracked paths relative to the work tree, `/`-separated and sorted.
+    paths: Vec<Vec<u8>>,
+}
+
+impl GitIndex {
+    /// Reads the index of the work tree rooted at `work_tree`, kept in
+    /// `git_dir`. A repository with nothing staged yet has no index, and
+    /// tracks nothing.
+    pub(crate) fn read(fs: &impl FileSystem, work_tree: &Path, git_dir: &Path) -> Result<Self> {
+        match fs.read(&git_file(git_dir, "index")) {
+            Ok(data) => Self::parse(work_tree.to_path_buf(), &data),
+            Err(report) if is_not_found(&report) => Ok(Self {
+                work_tree: work_tree.to_path_buf(),
+                paths: Vec::new(),
+            }),
+            Err(report) => Err(report),
+        }
+    }
+
+    /// Parses an index in version 2, 3 or 4 of the format, see
+    /// `gitformat-index(5)`. Extensions are ignored.
+    pub(crate) fn parse(work_tree: PathBuf, data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, position: 0 };
+        if reader.take(4)? != b"DIRC" {
+            bail!("Not a git index");
+        }
+        let version = reader.u32()?;
+        if !(2..=4).contains(&version) {
+            bail!("Unsupported git index version {}", version);
+        }
+        let count = reader.u32()? as usize;
+
+        let mut paths: Vec<Vec<u8>> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let start = reader.position;
+            // Stat data, mode and object id
+            reader.take(60)?;
+            let flags = reader.u16()?;
+            if version >= 3 && flags & EXTENDED_FLAG != 0 {
+                reader.take(2)?;
+            }
+            let path = if version == 4 {
+                // Prefix-compressed against the previous path, without padding
+                let previous = paths.last().map_or(&[][..], |p| &p[..]);
+                let strip = reader.varint()?;
+                let kept = previous
+                    .len()
+                    .checked_sub(strip)
+                    .ok_or_else(|| eyre!("Corrupted git index"))?;
+                let mut path = previous[..kept].to_vec();
+                path.extend_from_slice(reader.until_nul()?);
+                path
+            } else {
+                let path = reader.until_nul()?.to_vec();
+                // Entries are padded with 1 to 8 NULs to a multiple of 8 bytes,
+                // the first one ends the path.
+                let length = reader.position - start - 1;
+                reader.take(length / 8 * 8 + 8 - length - 1)?;
+                path
+            };
+            paths.push(path);
+        }
+        // Conflicts have an entry per stage
+        paths.sort();
+        paths.dedup();
+
+        Ok(Self { work_tree, paths })
+    }
+
+    /// Number of tracked files at `path` or under it.
+    pub fn tracked_under(&self, path: &Path) -> usize {
+        let Some(relative) = relative_key(&self.work_tree, path) else {
+            return 0;
+        };
+        if relative.is_empty() {
+            return self.paths.len();
+        }
+        let mut prefix = relative.clone();
+        prefix.push(b'/');
+
+        let exact = usize::from(self.paths.binary_search(&relative).is_ok());
+        let start = self.paths.partition_point(|p| *p < prefix);
+        let under = self.paths[start..]
+            .iter()
+            .take_while(|p| p.starts_with(&prefix))
+            .count();
+        exact + under
+    }
+}
+
+/// Git directory of the work tree rooted at `work_tree`, whose `.git` entry
+/// is `dot_git`.
+pub(crate) fn git_dir(
+    fs: &impl FileSystem,
+    work_tree: &Path,
+    dot_git: &FileInfo,
+) -> Result<PathBuf> {
+    match dot_git.kind {
+        // Worktrees and submodules have a `.git` file pointing to their git
+        // directory.
+        SimpleFileKind::File => {
+            let content = fs.read_to_string(dot_git)?;
+            let git_dir = content
+                .trim()
+                .strip_prefix("gitdir:")
+                .ok_or_else(|| eyre!("Unexpected content in {}", dot_git.path.display()))?;
+            Ok(work_tree.join(git_dir.trim()))
+        }
+        _ => Ok(dot_git.path.clone()),
+    }
+}
+
+/// Patterns of `.git/info/exclude`, empty when there is no such file.
+pub(crate) fn read_exclude(
+    fs: &impl FileSystem,
+    work_tree: &Path,
+    git_dir: &Path,
+) -> Result<IgnoreRules> {
+    let exclude = git_file(&git_dir.join("info"), "exclude");
+    match fs.read_to_string(&exclude) {
+        Ok(source) => Ok(IgnoreRules::parse(work_tree.to_path_buf(), &source)),
+        Err(report) if is_not_found(&report) => Ok(IgnoreRules::parse(work_tree.to_path_buf(), "")),
+        Err(report) => Err(report),
+    }
+}
+
+fn git_file(dir: &Path, name: &str) -> FileInfo {
+    FileInfo::new(dir.join(name), name.into(), SimpleFileKind::File)
+}
+
+/// `path` relative to `work_tree`, as stored in the index.
+fn relative_key(work_tree: &Path, path: &Path) -> Option<Vec<u8>> {
+    let relative = path.strip_prefix(work_tree).ok()?;
+    let components: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().as_encoded_bytes())
+        .collect();
+    Some(components.join(&b'/'))
+}
+
+fn is_not_found(report: &eyre::Report) -> bool {
+    report
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| eyre!("Truncated git index"))?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// Offset encoding of git: each continuation adds one before shifting,
+    /// so that every value has a single encoding.
+    fn varint(&mut self) -> Result<usize> {
+        let mut byte = self.take(1)?[0];
+        let mut value = usize::from(byte & 0x7f);
+        while byte & 0x80 != 0 {
+            byte = self.take(1)?[0];
+            value = ((value + 1) << 7) | usize::from(byte & 0x7f);
+        }
+        Ok(value)
+    }
+
+    /// Bytes up to the next NUL, which is consumed too.
+    fn until_nul(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        let length = rest
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| eyre!("Truncated git index"))?;
+        self.position += length + 1;
+        Ok(&rest[..length])
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::GitIndex;
+
+    /// Encodes an index listing `paths`, in order, with blank stat data.
+    pub(crate) fn encode_index(version: u32, paths: &[&str]) -> Vec<u8> {
+        let mut data = b"DIRC".to_vec();
+        data.extend(version.to_be_bytes());
+        data.extend((paths.len() as u32).to_be_bytes());
+        let mut previous = "";
+        for path in paths {
+            let start = data.len();
+            data.extend([0; 60]);
+            data.extend((path.len().min(0xfff) as u16).to_be_bytes());
+            if version == 4 {
+                let common = previous
+                    .bytes()
+                    .zip(path.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                // Small enough to fit a single varint byte
+                data.push((previous.len() - common) as u8);
+                data.extend(&path.as_bytes()[common..]);
+                data.push(0);
+            } else {
+                data.extend(path.as_bytes());
+                let length = data.len() - start;
+                data.extend(vec![0; 8 - length % 8]);
+            }
+            previous = path;
+        }
+        data
+    }
+
+    #[test]
+    fn counts_tracked_files_under_a_path() -> eyre::Result<()> {
+        let paths = [
+            "Cargo.toml",
+            "build/generated.rs",
+            "build/nested/more.rs",
+            "build.rs",
+            "builder/main.rs",
+            "src/main.rs",
+        ];
+        for version in [2, 3, 4] {
+            let index = GitIndex::parse(PathBuf::from("/repo"), &encode_index(version, &paths))?;
+
+            let tracked = |path: &str| index.tracked_under(Path::new(path));
+            assert_eq!(tracked("/repo/build"), 2);
+            assert_eq!(tracked("/repo/build.rs"), 1);
+            assert_eq!(tracked("/repo/target"), 0);
+            assert_eq!(tracked("/repo"), 6);
+            assert_eq!(tracked("/elsewhere/build"), 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_what_is_not_an_index() {
+        let root = PathBuf::from("/repo");
+        assert!(GitIndex::parse(root.clone(), b"not an index").is_err());
+
+        let mut truncated = encode_index(2, &["src/main.rs"]);
+        truncated.truncate(40);
+        assert!(GitIndex::parse(root, &truncated).is_err());
+    }
+}
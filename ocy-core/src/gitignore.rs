@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+/// Name of the per-directory ignore file of git.
+pub const GITIGNORE_FILE: &str = ".gitignore";
+
+const IGNORE_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Patterns of a `.gitignore` file, or of `.git/info/exclude`, see
+/// `gitignore(5)`.
+#[derive(Debug)]
+pub struct IgnoreRules {
+    /// Directory anchored patterns are relative to.
+    base: PathBuf,
+    patterns: Vec<IgnorePattern>,
+}
+
+#[derive(Debug)]
+struct IgnorePattern {
+    pattern: Pattern,
+    negated: bool,
+    /// Only matches directories, written with a trailing slash.
+    dir_only: bool,
+    /// Matches the path relative to the base rather than the name at any
+    /// depth, written with a slash elsewhere than at the end.
+    anchored: bool,
+}
+
+impl IgnoreRules {
+    /// Parses `source`, for patterns relative to `base`. Invalid patterns
+    /// are left out, as git does.
+    pub fn parse(base: PathBuf, source: &str) -> Self {
+        Self {
+            base,
+            patterns: source.lines().filter_map(IgnorePattern::parse).collect(),
+        }
+    }
+
+    /// Whether `path` is ignored, `Some(false)` when a negated pattern
+    /// includes it back, `None` when no pattern matches. The last matching
+    /// pattern wins.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(relative, is_dir))
+            .map(|p| !p.negated)
+    }
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let line = trim_trailing_spaces(line);
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        let pattern = Pattern::new(&unescape(line)).ok()?;
+        Some(Self {
+            pattern,
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            self.pattern
+                .matches_path_with(relative, IGNORE_MATCH_OPTIONS)
+        } else {
+            relative
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| self.pattern.matches_with(n, IGNORE_MATCH_OPTIONS))
+        }
+    }
+}
+
+/// Trailing spaces are dropped unless escaped with a backslash.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Turns the backslash escapes of gitignore into glob syntax, which has none.
+fn unescape(pattern: &str) -> String {
+    let mut unescaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('*' | '?' | '[' | ']')) => {
+                    unescaped.push('[');
+                    unescaped.push(c);
+                    unescaped.push(']');
+                }
+                Some(c) => unescaped.push(c),
+                None => {}
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::IgnoreRules;
+
+    #[test]
+    fn matches_like_git() {
+        let rules = IgnoreRules::parse(
+            PathBuf::from("/repo"),
+            r"
+# build outputs
+build/
+/out
+*.log
+!keep.log
+docs/_site
+**/generated
+\#notes
+",
+        );
+        let ignored = |path: &str, is_dir: bool| rules.matched(Path::new(path), is_dir);
+
+        assert_eq!(ignored("/repo/build", true), Some(true));
+        assert_eq!(ignored("/repo/sub/build", true), Some(true));
+        assert_eq!(ignored("/repo/build", false), None);
+        assert_eq!(ignored("/repo/out", true), Some(true));
+        assert_eq!(ignored("/repo/sub/out", true), None);
+        assert_eq!(ignored("/repo/sub/debug.log", false), Some(true));
+        assert_eq!(ignored("/repo/sub/keep.log", false), Some(false));
+        assert_eq!(ignored("/repo/docs/_site", true), Some(true));
+        assert_eq!(ignored("/repo/sub/docs/_site", true), None);
+        assert_eq!(ignored("/repo/generated", true), Some(true));
+        assert_eq!(ignored("/repo/a/b/generated", true), Some(true));
+        assert_eq!(ignored("/repo/#notes", false), Some(true));
+        assert_eq!(ignored("/repo/src", true), None);
+        assert_eq!(ignored("/elsewhere/build", true), None);
+    }
+
+    #[test]
+    fn trailing_spaces_are_trimmed_unless_escaped() {
+        let rules = IgnoreRules::parse(PathBuf::from("/repo"), "escaped\\ \ntrimmed   \n");
+        let ignored = |path: &str| rules.matched(Path::new(path), false);
+
+        assert_eq!(ignored("/repo/escaped "), Some(true));
+        assert_eq!(ignored("/repo/trimmed"), Some(true));
+        assert_eq!(ignored("/repo/trimmed "), None);
+    }
+}
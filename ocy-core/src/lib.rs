@@ -1,8 +1,21 @@
 pub mod cleaner;
 pub mod command;
+pub mod config;
+#[cfg(unix)]
+pub mod disk;
+pub mod dry_run;
 pub mod filesystem;
+mod git;
+mod gitignore;
+pub mod marker;
 pub mod matcher;
 pub mod models;
+pub mod quarantine;
+mod scope;
+pub mod selection;
 #[cfg(test)]
 mod test_utils;
+#[cfg(unix)]
+pub mod trash;
 pub mod walker;
+mod work_queue;
@@ -0,0 +1,149 @@
+use glob::Pattern;
+use regex::Regex;
+
+use crate::{
+    filesystem::FileSystem,
+    models::{FileInfo, SimpleFileKind},
+};
+
+/// Predicate telling whether a directory is a project a rule applies to,
+/// from its entries.
+pub enum Marker {
+    /// An entry of any kind named like the pattern.
+    Entry(Pattern),
+    /// A file named like the pattern, with matching content if given.
+    File(Pattern, Option<Content>),
+    /// A directory named like the pattern.
+    Directory(Pattern),
+    /// The name of the directory itself.
+    Name(Pattern),
+    /// The parent directory is marked.
+    Parent(Box<Marker>),
+    All(Vec<Marker>),
+    Any(Vec<Marker>),
+    Not(Box<Marker>),
+}
+
+/// What the content of a [`Marker::File`] must hold.
+pub enum Content {
+    Contains(String),
+    Matches(Regex),
+}
+
+impl From<Pattern> for Marker {
+    fn from(pattern: Pattern) -> Self {
+        Marker::Entry(pattern)
+    }
+}
+
+impl Marker {
+    /// Whether `dir`, made of `entries`, is marked. Files that cannot be read
+    /// and parents that cannot be listed do not match.
+    pub fn matches(&self, fs: &impl FileSystem, dir: &FileInfo, entries: &[FileInfo]) -> bool {
+        match self {
+            Marker::Entry(pattern) => entries.iter().any(|e| pattern.matches(&e.name)),
+            Marker::File(pattern, content) => entries
+                .iter()
+                .filter(|e| e.kind == SimpleFileKind::File && pattern.matches(&e.name))
+                .any(|e| content.as_ref().is_none_or(|c| c.found_in(fs, e))),
+            Marker::Directory(pattern) => entries
+                .iter()
+                .any(|e| e.kind == SimpleFileKind::Directory && pattern.matches(&e.name)),
+            Marker::Name(pattern) => dir
+                .path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| pattern.matches(n)),
+            Marker::Parent(marker) => {
+                let Some(path) = dir.path.parent() else {
+                    return false;
+                };
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let parent = FileInfo::new(path.to_path_buf(), name, SimpleFileKind::Directory);
+                fs.list_files(&parent)
+                    .is_ok_and(|entries| marker.matches(fs, &parent, &entries))
+            }
+            Marker::All(markers) => markers.iter().all(|m| m.matches(fs, dir, entries)),
+            Marker::Any(markers) => markers.iter().any(|m| m.matches(fs, dir, entries)),
+            Marker::Not(marker) => !marker.matches(fs, dir, entries),
+        }
+    }
+}
+
+impl Content {
+    fn found_in(&self, fs: &impl FileSystem, file: &FileInfo) -> bool {
+        let Ok(data) = fs.read(file) else {
+            return false;
+        };
+        let text = String::from_utf8_lossy(&data);
+        match self {
+            Content::Contains(needle) => text.contains(needle.as_str()),
+            Content::Matches(regex) => regex.is_match(&text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glob::Pattern;
+    use regex::Regex;
+
+    use crate::{
+        filesystem::FileSystem,
+        test_utils::{MockFS, MockFSNode},
+    };
+
+    use super::{Content, Marker};
+
+    fn pattern(p: &str) -> Pattern {
+        Pattern::new(p).unwrap()
+    }
+
+    #[test]
+    fn combines_predicates() -> eyre::Result<()> {
+        let fs = MockFS::new(MockFSNode::dir(
+            "/",
+            vec![MockFSNode::dir(
+                "home",
+                vec![MockFSNode::dir(
+                    "user",
+                    vec![
+                        MockFSNode::file_with_content("Cargo.toml", "[package]\nname = \"app\""),
+                        MockFSNode::file("CMakeCache.txt"),
+                        MockFSNode::dir("build", vec![MockFSNode::file("CMakeFiles")]),
+                    ],
+                )],
+            )],
+        ));
+        let dir = fs.current_directory()?;
+        let entries = fs.list_files(&dir)?;
+        let marked = |marker: Marker| marker.matches(&fs, &dir, &entries);
+
+        assert!(marked(Marker::All(vec![
+            pattern("CMakeCache.txt").into(),
+            Marker::Not(Box::new(pattern(".keep-build").into())),
+        ])));
+        assert!(marked(Marker::File(
+            pattern("Cargo.toml"),
+            Some(Content::Contains("[package]".into()))
+        )));
+        assert!(!marked(Marker::File(
+            pattern("Cargo.toml"),
+            Some(Content::Matches(Regex::new(r"(?m)^\[workspace\]")?))
+        )));
+        assert!(!marked(Marker::File(pattern("build"), None)));
+        assert!(marked(Marker::Directory(pattern("build"))));
+        assert!(marked(Marker::Name(pattern("user"))));
+        assert!(marked(Marker::Parent(Box::new(Marker::Directory(
+            pattern("user")
+        )))));
+        assert!(!marked(Marker::Any(vec![
+            pattern("package.json").into(),
+            Marker::Parent(Box::new(Marker::Name(pattern("src")))),
+        ])));
+        Ok(())
+    }
+}
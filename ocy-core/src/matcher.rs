@@ -1,47 +1,228 @@
 use std::sync::Arc;
 
+use eyre::{bail, Context, Result};
 use glob::Pattern;
 
-use crate::models::FileInfo;
+use crate::{
+    filesystem::FileSystem,
+    marker::Marker,
+    models::{FileInfo, SimpleFileKind},
+};
 
 pub struct Matcher {
     pub name: Arc<str>,
-    pub to_match: Pattern,
+    pub to_match: Marker,
     pub clean_strategy: CleanStrategy,
 }
 pub enum CleanStrategy {
     Remove(RemovalPattern),
+    /// Remove the directories git ignores, like `git clean -dX` but leaving
+    /// ignored files alone, as they may hold secrets. In git work trees only.
+    RemoveIgnored(IgnoredExclusions),
     RunCommand(Arc<str>),
 }
 
-pub struct RemovalPattern(Pattern);
+/// What a [`CleanStrategy::Remove`] rule removes: entries of the project
+/// matching a name, or paths relative to the project matching a glob whose
+/// `**` components match any number of directories, e.g. `android/app/build`
+/// or `**/build`. A trailing slash only matches directories.
+///
+/// Nested paths are looked for in the directories the walk would enter, see
+/// [`RemovalPattern::find_files_to_remove`].
+pub struct RemovalPattern {
+    components: Vec<Component>,
+    dir_only: bool,
+}
+
+enum Component {
+    Name(Pattern),
+    AnyDirectories,
+}
+
+/// Names of directories never proposed by [`CleanStrategy::RemoveIgnored`],
+/// although ignored: secrets and IDE settings are not build outputs.
+pub const DEFAULT_IGNORED_EXCLUSIONS: &[&str] = &[
+    ".env", ".env.*", ".ssh", ".gnupg", ".aws", "secrets", ".idea", ".vscode", ".vs", ".fleet",
+];
+
+pub struct IgnoredExclusions(Vec<Pattern>);
 
 impl Matcher {
-    pub fn with_remove_strategy(name: Arc<str>, to_match: Pattern, to_remove: Pattern) -> Self {
-        let clean_strategy = CleanStrategy::Remove(RemovalPattern(to_remove));
+    /// Fails when `to_remove` is not a valid [`RemovalPattern`], e.g. with a
+    /// slash inside brackets.
+    pub fn with_remove_strategy(
+        name: Arc<str>,
+        to_match: impl Into<Marker>,
+        to_remove: Pattern,
+    ) -> Result<Self> {
+        let to_remove = RemovalPattern::parse(to_remove.as_str())?;
+        Ok(Self::with_removal_pattern(name, to_match, to_remove))
+    }
+
+    pub fn with_removal_pattern(
+        name: Arc<str>,
+        to_match: impl Into<Marker>,
+        to_remove: RemovalPattern,
+    ) -> Self {
         Self {
             name,
-            to_match,
-            clean_strategy,
+            to_match: to_match.into(),
+            clean_strategy: CleanStrategy::Remove(to_remove),
         }
     }
 
-    pub fn with_command_strategy(name: Arc<str>, to_match: Pattern, command: String) -> Self {
+    pub fn with_command_strategy(
+        name: Arc<str>,
+        to_match: impl Into<Marker>,
+        command: String,
+    ) -> Self {
         let clean_strategy = CleanStrategy::RunCommand(command.into());
         Self {
             name,
-            to_match,
+            to_match: to_match.into(),
+            clean_strategy,
+        }
+    }
+
+    /// A rule removing what git ignores, save entries named like
+    /// `excluded`, on top of [`DEFAULT_IGNORED_EXCLUSIONS`].
+    pub fn with_ignored_strategy(
+        name: Arc<str>,
+        to_match: impl Into<Marker>,
+        excluded: Vec<Pattern>,
+    ) -> Self {
+        let mut exclusions: Vec<_> = DEFAULT_IGNORED_EXCLUSIONS
+            .iter()
+            .map(|p| Pattern::new(p).expect("valid default exclusion"))
+            .collect();
+        exclusions.extend(excluded);
+        let clean_strategy = CleanStrategy::RemoveIgnored(IgnoredExclusions(exclusions));
+        Self {
+            name,
+            to_match: to_match.into(),
             clean_strategy,
         }
     }
 
-    pub fn any_entry_match(&self, entries: &[FileInfo]) -> bool {
-        entries.iter().any(|e| self.to_match.matches(&e.name))
+    /// Whether this rule applies to `dir`, made of `entries`.
+    pub fn matches_project(
+        &self,
+        fs: &impl FileSystem,
+        dir: &FileInfo,
+        entries: &[FileInfo],
+    ) -> bool {
+        self.to_match.matches(fs, dir, entries)
     }
 }
 
 impl RemovalPattern {
-    pub fn find_files_to_remove(&self, entries: Vec<FileInfo>) -> (Vec<FileInfo>, Vec<FileInfo>) {
-        entries.into_iter().partition(|e| self.0.matches(&e.name))
+    /// Parses a pattern made of glob components separated by slashes.
+    pub fn parse(source: &str) -> Result<Self> {
+        let dir_only = source.ends_with('/');
+        let components = source
+            .trim_end_matches('/')
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .map(|c| match c {
+                "**" => Ok(Component::AnyDirectories),
+                c => Pattern::new(c)
+                    .map(Component::Name)
+                    .wrap_err_with(|| format!("invalid component `{}`", c)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if components.is_empty() {
+            bail!("no path to match");
+        }
+        Ok(Self {
+            components,
+            dir_only,
+        })
+    }
+
+    /// Splits the entries of a project into what to remove and what remains.
+    /// Paths nested in the project are looked for below `entries`, through
+    /// `list` returning the entries of a directory, or none when the walk
+    /// would not enter it. Its flag tells whether the pattern names the
+    /// directory with a leading dot, which enters it although hidden. A path
+    /// nested in another one found is left out.
+    pub fn find_files_to_remove(
+        &self,
+        entries: Vec<FileInfo>,
+        mut list: impl FnMut(&FileInfo, bool) -> Vec<FileInfo>,
+    ) -> (Vec<FileInfo>, Vec<FileInfo>) {
+        if let [Component::Name(pattern)] = &self.components[..] {
+            return entries
+                .into_iter()
+                .partition(|e| pattern.matches(&e.name) && self.kind_matches(e));
+        }
+
+        let mut found = Vec::new();
+        self.expand(&entries, &self.components, &mut list, &mut found);
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut to_remove: Vec<FileInfo> = Vec::with_capacity(found.len());
+        for file in found {
+            if !to_remove
+                .last()
+                .is_some_and(|last| file.path.starts_with(&last.path))
+            {
+                to_remove.push(file);
+            }
+        }
+        let remaining = entries
+            .into_iter()
+            .filter(|e| !to_remove.iter().any(|f| f.path == e.path))
+            .collect();
+        (to_remove, remaining)
+    }
+
+    fn expand(
+        &self,
+        entries: &[FileInfo],
+        components: &[Component],
+        list: &mut impl FnMut(&FileInfo, bool) -> Vec<FileInfo>,
+        found: &mut Vec<FileInfo>,
+    ) {
+        let Some((first, rest)) = components.split_first() else {
+            return;
+        };
+        match first {
+            Component::AnyDirectories if rest.is_empty() => {
+                found.extend(entries.iter().filter(|e| self.kind_matches(e)).cloned());
+            }
+            Component::AnyDirectories => {
+                let before = found.len();
+                self.expand(entries, rest, list, found);
+                let not_found: Vec<_> = entries
+                    .iter()
+                    .filter(|e| !found[before..].iter().any(|f| f.path == e.path))
+                    .collect();
+                for entry in not_found {
+                    let children = list(entry, false);
+                    self.expand(&children, components, list, found);
+                }
+            }
+            Component::Name(pattern) => {
+                for entry in entries.iter().filter(|e| pattern.matches(&e.name)) {
+                    if rest.is_empty() {
+                        if self.kind_matches(entry) {
+                            found.push(entry.clone());
+                        }
+                    } else {
+                        let children = list(entry, pattern.as_str().starts_with('.'));
+                        self.expand(&children, rest, list, found);
+                    }
+                }
+            }
+        }
+    }
+
+    fn kind_matches(&self, entry: &FileInfo) -> bool {
+        !self.dir_only || entry.kind == SimpleFileKind::Directory
+    }
+}
+
+impl IgnoredExclusions {
+    pub fn excludes(&self, entry: &FileInfo) -> bool {
+        self.0.iter().any(|p| p.matches(&entry.name))
     }
 }
@@ -1,24 +1,56 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc, time::SystemTime};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "snake_case"))]
 pub enum SimpleFileKind {
     File,
     Directory,
+    /// A symbolic link, whatever it points to.
+    Symlink,
 }
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct FileInfo {
     pub path: PathBuf,
     pub name: String,
     pub kind: SimpleFileKind,
+    /// Last modification time, when the file system reports it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub modified: Option<SystemTime>,
+    /// Device holding the file, when the file system reports it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub device: Option<u64>,
 }
 
 impl FileInfo {
     pub(crate) fn new(path: PathBuf, name: String, kind: SimpleFileKind) -> Self {
-        Self { path, name, kind }
+        Self {
+            path,
+            name,
+            kind,
+            modified: None,
+            device: None,
+        }
+    }
+
+    pub(crate) fn with_modified(self, modified: Option<SystemTime>) -> Self {
+        Self { modified, ..self }
+    }
+
+    pub(crate) fn with_device(self, device: Option<u64>) -> Self {
+        Self { device, ..self }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
 pub enum RemovalAction {
     Delete {
         file_info: FileInfo,
@@ -30,10 +62,19 @@ pub enum RemovalAction {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct RemovalCandidate {
     pub matcher_name: Arc<str>,
+    #[cfg_attr(feature = "serde", serde(flatten))]
     pub action: RemovalAction,
+    /// Last modification time of the project, removal targets excluded,
+    /// when the walk was asked to track it.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub project_modified: Option<SystemTime>,
+    /// Files under the target tracked by git, which cleaning would delete
+    /// from the work tree. Only counted when the walk checks git.
+    pub tracked_files: usize,
 }
 
 impl RemovalCandidate {
@@ -45,6 +86,8 @@ impl RemovalCandidate {
         Self {
             matcher_name,
             action,
+            project_modified: None,
+            tracked_files: 0,
         }
     }
 
@@ -53,6 +96,49 @@ impl RemovalCandidate {
         Self {
             matcher_name,
             action,
+            project_modified: None,
+            tracked_files: 0,
+        }
+    }
+
+    pub(crate) fn with_project_modified(self, project_modified: Option<SystemTime>) -> Self {
+        Self {
+            project_modified,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_tracked_files(self, tracked_files: usize) -> Self {
+        Self {
+            tracked_files,
+            ..self
+        }
+    }
+
+    /// Whether cleaning the candidate would delete files tracked by git.
+    pub fn is_tracked(&self) -> bool {
+        self.tracked_files > 0
+    }
+
+    /// Sets the size of a removal, commands have none.
+    pub(crate) fn with_file_size(self, size: Option<u64>) -> Self {
+        match self.action {
+            RemovalAction::Delete { file_info, .. } => Self {
+                action: RemovalAction::Delete {
+                    file_info,
+                    file_size: size,
+                },
+                ..self
+            },
+            RemovalAction::RunCommand { .. } => self,
+        }
+    }
+
+    /// File removed by the candidate, or directory its command runs in.
+    pub fn target(&self) -> &FileInfo {
+        match &self.action {
+            RemovalAction::Delete { file_info, .. } => file_info,
+            RemovalAction::RunCommand { work_dir, .. } => work_dir,
         }
     }
 
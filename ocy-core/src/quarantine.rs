@@ -0,0 +1,417 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{bail, eyre, Context, Result};
+use toml::{Table, Value};
+
+use crate::{
+    filesystem::{available_space, data_home, move_path, FileSystemClean, RealFileSystem},
+    models::{FileInfo, RemovalCandidate},
+};
+
+const MANIFEST_FILE: &str = "manifest.toml";
+const LOCK_FILE: &str = "manifest.lock";
+const ITEMS_DIR: &str = "items";
+
+/// A file moved into the quarantine.
+#[derive(Debug, Clone)]
+pub struct QuarantineEntry {
+    pub id: String,
+    pub original_path: PathBuf,
+    pub matcher_name: String,
+    pub size: Option<u64>,
+    pub removed_at: SystemTime,
+}
+
+/// Store keeping removed files until they are restored or purged.
+///
+/// Each item is moved to its own `items/<id>` directory, and recorded in a
+/// `manifest.toml` with its original path, matcher, size and removal time.
+/// Updates of the manifest hold an advisory lock on `manifest.lock`, so that
+/// items can be stored from several threads and processes.
+pub struct Quarantine {
+    root: PathBuf,
+    /// Measures what commands free.
+    sizes: RealFileSystem,
+}
+
+impl Quarantine {
+    /// The quarantine in `$XDG_DATA_HOME/ocy/quarantine`.
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_root(data_home()?.join("ocy").join("quarantine")))
+    }
+
+    pub fn with_root(root: PathBuf) -> Self {
+        Self {
+            root,
+            sizes: RealFileSystem::default(),
+        }
+    }
+
+    /// Measures what commands free with `sizes`, in the size mode of the
+    /// walk rather than in apparent sizes.
+    pub fn with_sizes(self, sizes: RealFileSystem) -> Self {
+        Self { sizes, ..self }
+    }
+
+    pub fn entries(&self) -> Result<Vec<QuarantineEntry>> {
+        let manifest = self.root.join(MANIFEST_FILE);
+        let source = match fs::read_to_string(&manifest) {
+            Ok(source) => source,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        parse_manifest(&source)
+            .wrap_err_with(|| format!("Invalid quarantine manifest {}", manifest.display()))
+    }
+
+    /// Moves `file` into the quarantine. Paths that are not valid UTF-8 are
+    /// refused, the manifest could not record them for restoring.
+    pub fn store(&self, candidate: &RemovalCandidate, file: &FileInfo) -> Result<QuarantineEntry> {
+        if file.path.to_str().is_none() {
+            bail!(
+                "Cannot quarantine {}, its path is not valid UTF-8",
+                file.path.display()
+            );
+        }
+        let items = self.root.join(ITEMS_DIR);
+        fs::create_dir_all(&items)?;
+
+        let removed_at = SystemTime::now();
+        let id = reserve_id(&items, removed_at)?;
+        let name = file
+            .path
+            .file_name()
+            .ok_or_else(|| eyre!("Cannot quarantine {}", file.path.display()))?;
+        let stored_path = items.join(&id).join(name);
+
+        if let Err(e) = move_path(&file.path, &stored_path) {
+            let _ = fs::remove_dir(items.join(&id));
+            return Err(e);
+        }
+
+        let entry = QuarantineEntry {
+            id,
+            original_path: file.path.clone(),
+            matcher_name: candidate.matcher_name.to_string(),
+            size: candidate.file_size(),
+            removed_at,
+        };
+
+        let updated = self.lock_manifest().and_then(|_lock| {
+            let mut entries = self.entries()?;
+            entries.push(entry.clone());
+            self.write_manifest(&entries)
+        });
+        if let Err(e) = updated {
+            let _ = move_path(&stored_path, &file.path);
+            let _ = fs::remove_dir(items.join(&entry.id));
+            return Err(e);
+        }
+        Ok(entry)
+    }
+
+    /// Moves an item back to its original path. `query` is either the id of
+    /// the item, or its original path in which case the latest item removed
+    /// from there is restored.
+    pub fn restore(&self, query: &str) -> Result<QuarantineEntry> {
+        let _lock = self.lock_manifest()?;
+        let mut entries = self.entries()?;
+        let index = entries
+            .iter()
+            .rposition(|e| e.id == query)
+            .or_else(|| {
+                entries
+                    .iter()
+                    .rposition(|e| e.original_path == Path::new(query))
+            })
+            .ok_or_else(|| eyre!("No quarantined item matches `{}`", query))?;
+
+        let entry = &entries[index];
+        if fs::symlink_metadata(&entry.original_path).is_ok() {
+            bail!("{} already exists", entry.original_path.display());
+        }
+        if let Some(parent) = entry.original_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let item_dir = self.item_dir(entry);
+        let name = entry
+            .original_path
+            .file_name()
+            .ok_or_else(|| eyre!("Invalid original path {}", entry.original_path.display()))?;
+        move_path(&item_dir.join(name), &entry.original_path)?;
+        fs::remove_dir(&item_dir)?;
+
+        let entry = entries.remove(index);
+        self.write_manifest(&entries)?;
+        Ok(entry)
+    }
+
+    /// Permanently deletes the items removed more than `older_than` ago.
+    /// Should one fail, those deleted before it are still dropped from the
+    /// manifest.
+    pub fn purge(&self, older_than: Duration) -> Result<Vec<QuarantineEntry>> {
+        let _lock = self.lock_manifest()?;
+        let now = SystemTime::now();
+        let entries = self.entries()?;
+
+        let mut purged = Vec::new();
+        let mut result = Ok(());
+        for entry in entries.iter().filter(|e| {
+            now.duration_since(e.removed_at)
+                .is_ok_and(|age| age >= older_than)
+        }) {
+            match fs::remove_dir_all(self.item_dir(entry)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    result = Err(e).wrap_err_with(|| format!("Cannot purge item {}", entry.id));
+                    break;
+                }
+            }
+            purged.push(entry.clone());
+        }
+
+        let purged_ids: HashSet<_> = purged.iter().map(|e| e.id.as_str()).collect();
+        let kept: Vec<_> = entries
+            .iter()
+            .filter(|e| !purged_ids.contains(e.id.as_str()))
+            .cloned()
+            .collect();
+        self.write_manifest(&kept)?;
+        result.map(|()| purged)
+    }
+
+    fn item_dir(&self, entry: &QuarantineEntry) -> PathBuf {
+        self.root.join(ITEMS_DIR).join(&entry.id)
+    }
+
+    /// Locks the manifest against other threads and processes until the
+    /// returned file is dropped.
+    fn lock_manifest(&self) -> Result<File> {
+        fs::create_dir_all(&self.root)?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.root.join(LOCK_FILE))?;
+        lock.lock()?;
+        Ok(lock)
+    }
+
+    /// Must be called with the manifest locked.
+    fn write_manifest(&self, entries: &[QuarantineEntry]) -> Result<()> {
+        let items = entries.iter().map(entry_to_value).collect();
+        let mut table = Table::new();
+        table.insert("item".into(), Value::Array(items));
+
+        let manifest = self.root.join(MANIFEST_FILE);
+        // Renamed over the manifest once written, so that readers never see
+        // it half written
+        let temp = self.root.join(format!("{}.tmp", MANIFEST_FILE));
+        fs::write(&temp, table.to_string())?;
+        fs::rename(&temp, &manifest)?;
+        Ok(())
+    }
+}
+
+impl FileSystemClean for Quarantine {
+    fn remove_file(&self, file: &FileInfo) -> Result<()> {
+        let candidate = RemovalCandidate::new("".into(), file.clone(), None);
+        self.store(&candidate, file).map(|_| ())
+    }
+
+    fn remove_candidate(&self, candidate: &RemovalCandidate, file: &FileInfo) -> Result<()> {
+        self.store(candidate, file).map(|_| ())
+    }
+
+    fn measure_size(&self, file: &FileInfo) -> Option<u64> {
+        self.sizes.measure_size(file)
+    }
+
+    fn moves_files(&self) -> bool {
+        true
+    }
+
+    fn available_space(&self, path: &Path) -> Option<u64> {
+        available_space(path)
+    }
+}
+
+/// Creates a new item directory, named after the removal time in
+/// milliseconds.
+fn reserve_id(items: &Path, removed_at: SystemTime) -> Result<String> {
+    let mut millis = removed_at.duration_since(UNIX_EPOCH)?.as_millis();
+    loop {
+        let id = format!("{:x}", millis);
+        match fs::create_dir(items.join(&id)) {
+            Ok(()) => return Ok(id),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => millis += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn entry_to_value(entry: &QuarantineEntry) -> Value {
+    let mut table = Table::new();
+    table.insert("id".into(), entry.id.clone().into());
+    table.insert(
+        "path".into(),
+        entry
+            .original_path
+            .to_str()
+            .expect("quarantined paths are valid UTF-8")
+            .into(),
+    );
+    table.insert("matcher".into(), entry.matcher_name.clone().into());
+    if let Some(size) = entry.size {
+        table.insert("size".into(), Value::Integer(size as i64));
+    }
+    let removed_at = entry
+        .removed_at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    table.insert("removed_at".into(), Value::Integer(removed_at as i64));
+    Value::Table(table)
+}
+
+fn parse_manifest(source: &str) -> Result<Vec<QuarantineEntry>> {
+    let mut table: Table = source.parse()?;
+    let items = match table.remove("item") {
+        None => return Ok(Vec::new()),
+        Some(Value::Array(items)) => items,
+        Some(_) => bail!("`item` must be an array of tables"),
+    };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            parse_entry(item).wrap_err_with(|| format!("Invalid item #{}", index + 1))
+        })
+        .collect()
+}
+
+fn parse_entry(value: Value) -> Result<QuarantineEntry> {
+    let str_field = |key: &str| {
+        value
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| eyre!("missing `{}`", key))
+    };
+    let int_field = |key: &str| value.get(key).and_then(Value::as_integer);
+
+    let removed_at = int_field("removed_at").ok_or_else(|| eyre!("missing `removed_at`"))?;
+    Ok(QuarantineEntry {
+        id: str_field("id")?,
+        original_path: str_field("path")?.into(),
+        matcher_name: str_field("matcher")?,
+        size: int_field("size").map(|s| s as u64),
+        removed_at: UNIX_EPOCH + Duration::from_secs(removed_at as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, thread, time::Duration};
+
+    use crate::models::{FileInfo, RemovalCandidate, SimpleFileKind};
+
+    use super::Quarantine;
+
+    #[test]
+    fn stored_items_can_be_restored_or_purged() -> eyre::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let quarantine = Quarantine::with_root(tmp.path().join("quarantine"));
+
+        let mut ids = Vec::new();
+        for project in ["a", "b"] {
+            let target = tmp.path().join(project).join("target");
+            fs::create_dir_all(&target)?;
+            fs::write(target.join("out"), "x")?;
+            let file = FileInfo::new(target.clone(), "target".into(), SimpleFileKind::Directory);
+            let candidate = RemovalCandidate::new("Cargo".into(), file.clone(), Some(1));
+            ids.push(quarantine.store(&candidate, &file)?.id);
+            assert!(!target.exists());
+        }
+
+        let entries = quarantine.entries()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].matcher_name, "Cargo");
+        assert_eq!(entries[0].size, Some(1));
+
+        let restored_path = tmp.path().join("b/target");
+        let restored = quarantine.restore(restored_path.to_str().unwrap())?;
+        assert_eq!(restored.id, ids[1]);
+        assert!(restored_path.join("out").is_file());
+
+        assert!(quarantine.purge(Duration::from_secs(3600))?.is_empty());
+        let purged = quarantine.purge(Duration::ZERO)?;
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].id, ids[0]);
+        assert!(quarantine.entries()?.is_empty());
+        assert!(!tmp.path().join("quarantine/items").join(&ids[0]).exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_that_are_not_utf8_are_refused() -> eyre::Result<()> {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let tmp = tempfile::tempdir()?;
+        let quarantine = Quarantine::with_root(tmp.path().join("quarantine"));
+        let target = tmp
+            .path()
+            .join(OsStr::from_bytes(b"caf\xe9"))
+            .join("target");
+        fs::create_dir_all(&target)?;
+        let file = FileInfo::new(target.clone(), "target".into(), SimpleFileKind::Directory);
+        let candidate = RemovalCandidate::new("Cargo".into(), file.clone(), None);
+
+        assert!(quarantine.store(&candidate, &file).is_err());
+        assert!(target.is_dir());
+        assert!(quarantine.entries()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn items_stored_concurrently_are_all_recorded() -> eyre::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().join("quarantine");
+
+        let ids: Vec<String> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|project| {
+                    let target = tmp.path().join(project.to_string()).join("target");
+                    // One quarantine each, as separate processes would have
+                    let quarantine = Quarantine::with_root(root.clone());
+                    scope.spawn(move || {
+                        fs::create_dir_all(&target)?;
+                        let file =
+                            FileInfo::new(target, "target".into(), SimpleFileKind::Directory);
+                        let candidate = RemovalCandidate::new("Cargo".into(), file.clone(), None);
+                        quarantine.store(&candidate, &file).map(|e| e.id)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<eyre::Result<_>>()
+        })?;
+
+        let quarantine = Quarantine::with_root(root);
+        let recorded: HashSet<_> = quarantine.entries()?.into_iter().map(|e| e.id).collect();
+        assert_eq!(recorded, ids.into_iter().collect());
+        assert_eq!(recorded.len(), 8);
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::{
+    config::RulesConfig,
+    git::GitIndex,
+    gitignore::IgnoreRules,
+    matcher::{CleanStrategy, Matcher},
+};
+
+/// Name of the per-directory rules file.
+pub const LOCAL_RULES_FILE: &str = ".ocy.toml";
+
+const PROTECT_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Rules in effect for a directory subtree.
+///
+/// Each `.ocy.toml` found during the walk creates a nested scope on top of
+/// the enclosing one, so settings apply to the directory holding the file and
+/// everything below it, the closest file taking precedence. Git work trees
+/// nest a scope too, knowing what is tracked below them, and so do their
+/// `.gitignore` files when a rule removes what git ignores.
+pub struct RuleScope {
+    base: PathBuf,
+    matchers: Vec<Arc<Matcher>>,
+    protected: Vec<ProtectPattern>,
+    git_index: Option<GitIndex>,
+    ignored: Option<IgnoreRules>,
+    parent: Option<Arc<RuleScope>>,
+}
+
+impl RuleScope {
+    pub fn root(matchers: Vec<Matcher>) -> Arc<Self> {
+        Arc::new(Self {
+            base: PathBuf::new(),
+            matchers: matchers.into_iter().map(Arc::new).collect(),
+            protected: Vec::new(),
+            git_index: None,
+            ignored: None,
+            parent: None,
+        })
+    }
+
+    pub fn nested(self: &Arc<Self>, base: PathBuf, config: RulesConfig) -> Arc<Self> {
+        let mut matchers: Vec<_> = self
+            .matchers
+            .iter()
+            .filter(|m| config.keeps(&m.name))
+            .cloned()
+            .collect();
+        matchers.extend(config.rules.into_iter().map(Arc::new));
+
+        Arc::new(Self {
+            base,
+            matchers,
+            protected: config
+                .protected
+                .into_iter()
+                .map(ProtectPattern::new)
+                .collect(),
+            git_index: None,
+            ignored: None,
+            parent: Some(self.clone()),
+        })
+    }
+
+    /// Scope of the git work tree at `base`, with the same rules, ignoring
+    /// what `excluded` lists as `.git/info/exclude` does.
+    pub fn with_git_index(
+        self: &Arc<Self>,
+        base: PathBuf,
+        git_index: GitIndex,
+        excluded: Option<IgnoreRules>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            base,
+            matchers: self.matchers.clone(),
+            protected: Vec::new(),
+            git_index: Some(git_index),
+            ignored: excluded,
+            parent: Some(self.clone()),
+        })
+    }
+
+    /// Scope of a directory with a `.gitignore`, with the same rules.
+    pub fn with_ignore_rules(self: &Arc<Self>, base: PathBuf, ignored: IgnoreRules) -> Arc<Self> {
+        Arc::new(Self {
+            base,
+            matchers: self.matchers.clone(),
+            protected: Vec::new(),
+            git_index: None,
+            ignored: Some(ignored),
+            parent: Some(self.clone()),
+        })
+    }
+
+    pub fn matchers(&self) -> &[Arc<Matcher>] {
+        &self.matchers
+    }
+
+    /// Whether `path` is protected by this scope or an enclosing one.
+    ///
+    /// As in `.gitignore`, a pattern without a slash matches the file name at
+    /// any depth, otherwise it matches the path relative to the directory of
+    /// the `.ocy.toml` declaring it.
+    pub fn is_protected(&self, path: &Path) -> bool {
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            if current.protects(path) {
+                return true;
+            }
+            scope = current.parent.as_deref();
+        }
+        false
+    }
+
+    /// Number of files at `path` or under it tracked by the closest
+    /// enclosing git work tree.
+    pub fn tracked_files(&self, path: &Path) -> usize {
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            if let Some(git_index) = &current.git_index {
+                return git_index.tracked_under(path);
+            }
+            scope = current.parent.as_deref();
+        }
+        0
+    }
+
+    /// Whether a rule of this scope removes what git ignores.
+    pub fn removes_ignored(&self) -> bool {
+        self.matchers
+            .iter()
+            .any(|m| matches!(m.clean_strategy, CleanStrategy::RemoveIgnored(_)))
+    }
+
+    /// Whether git ignores `path` in the closest enclosing work tree: the
+    /// closest `.gitignore` matching it decides, `.git/info/exclude` last.
+    /// Nothing is ignored outside of work trees.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            let matched = current
+                .ignored
+                .as_ref()
+                .and_then(|ignored| ignored.matched(path, is_dir));
+            if let Some(ignored) = matched {
+                return ignored && self.in_work_tree();
+            }
+            if current.git_index.is_some() {
+                return false;
+            }
+            scope = current.parent.as_deref();
+        }
+        false
+    }
+
+    fn in_work_tree(&self) -> bool {
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            if current.git_index.is_some() {
+                return true;
+            }
+            scope = current.parent.as_deref();
+        }
+        false
+    }
+
+    fn protects(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        self.protected.iter().any(|p| match p {
+            ProtectPattern::Anchored(p) => p.matches_path_with(relative, PROTECT_MATCH_OPTIONS),
+            ProtectPattern::Name(p) => relative
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| p.matches(n)),
+        })
+    }
+}
+
+enum ProtectPattern {
+    Anchored(Pattern),
+    Name(Pattern),
+}
+
+impl ProtectPattern {
+    fn new(pattern: Pattern) -> Self {
+        match pattern.as_str().strip_prefix('/') {
+            Some(anchored) => Pattern::new(anchored)
+                .map(ProtectPattern::Anchored)
+                .unwrap_or(ProtectPattern::Name(pattern)),
+            None if pattern.as_str().contains('/') => ProtectPattern::Anchored(pattern),
+            None => ProtectPattern::Name(pattern),
+        }
+    }
+}
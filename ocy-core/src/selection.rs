@@ -0,0 +1,140 @@
+use std::cmp::Reverse;
+
+use crate::models::RemovalCandidate;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Projects left untouched for the longest time first.
+    Oldest,
+    /// Largest candidates first.
+    #[default]
+    Largest,
+}
+
+/// Candidates selected to reclaim a given amount of space.
+#[derive(Debug)]
+pub struct Budget {
+    pub target: u64,
+    pub selected: Vec<RemovalCandidate>,
+    pub untouched: Vec<RemovalCandidate>,
+}
+
+impl Budget {
+    /// Estimated space reclaimed by the selected candidates.
+    pub fn reclaimed(&self) -> u64 {
+        self.selected.iter().map(|c| c.estimate_file_size()).sum()
+    }
+
+    pub fn is_met(&self) -> bool {
+        self.reclaimed() >= self.target
+    }
+}
+
+/// Orders candidates following `strategy` and selects the shortest prefix
+/// reclaiming at least `target` bytes, or all of them when they fall short.
+///
+/// [`Strategy::Oldest`] relies on [`RemovalCandidate::project_modified`];
+/// candidates of unknown age or size rank last.
+pub fn reclaim(mut candidates: Vec<RemovalCandidate>, target: u64, strategy: Strategy) -> Budget {
+    match strategy {
+        Strategy::Oldest => {
+            candidates.sort_by_key(|c| (c.project_modified.is_none(), c.project_modified))
+        }
+        Strategy::Largest => candidates.sort_by_key(|c| Reverse(c.file_size())),
+    }
+
+    let mut reclaimed = 0;
+    let count = candidates
+        .iter()
+        .take_while(|c| {
+            let needed = reclaimed < target;
+            reclaimed += c.estimate_file_size();
+            needed
+        })
+        .count();
+    let untouched = candidates.split_off(count);
+    Budget {
+        target,
+        selected: candidates,
+        untouched,
+    }
+}
+
+/// Keeps the `n` largest candidates, largest first. Candidates of unknown
+/// size rank last.
+pub fn largest(mut candidates: Vec<RemovalCandidate>, n: usize) -> Vec<RemovalCandidate> {
+    candidates.sort_by_key(|c| Reverse(c.file_size()));
+    candidates.truncate(n);
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::models::{FileInfo, RemovalAction, RemovalCandidate, SimpleFileKind};
+
+    use super::{largest, reclaim, Strategy};
+
+    fn candidate(name: &str, size: Option<u64>) -> RemovalCandidate {
+        let file = FileInfo::new(PathBuf::from(name), name.into(), SimpleFileKind::Directory);
+        RemovalCandidate::new("Cargo".into(), file, size)
+    }
+
+    fn names(candidates: Vec<RemovalCandidate>) -> Vec<String> {
+        candidates
+            .into_iter()
+            .map(|c| match c.action {
+                RemovalAction::Delete { file_info, .. } => file_info.name,
+                RemovalAction::RunCommand { .. } => panic!("should be delete"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_largest_candidates() {
+        let candidates = vec![
+            candidate("unknown", None),
+            candidate("small", Some(10)),
+            candidate("large", Some(1000)),
+            candidate("medium", Some(100)),
+        ];
+
+        assert_eq!(
+            names(largest(candidates, 3)),
+            vec!["large", "medium", "small"]
+        );
+    }
+
+    #[test]
+    fn reclaims_the_target_with_the_oldest_projects() {
+        let days_ago = |days: u64| SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+        let candidates = vec![
+            candidate("recent", Some(500)).with_project_modified(Some(days_ago(1))),
+            candidate("unknown", Some(500)),
+            candidate("old", Some(100)).with_project_modified(Some(days_ago(30))),
+            candidate("older", Some(100)).with_project_modified(Some(days_ago(60))),
+        ];
+
+        let budget = reclaim(candidates, 150, Strategy::Oldest);
+
+        assert!(budget.is_met());
+        assert_eq!(budget.reclaimed(), 200);
+        assert_eq!(names(budget.selected), vec!["older", "old"]);
+        assert_eq!(names(budget.untouched), vec!["recent", "unknown"]);
+    }
+
+    #[test]
+    fn selects_everything_when_the_target_cannot_be_met() {
+        let candidates = vec![candidate("small", Some(10)), candidate("large", Some(100))];
+
+        let budget = reclaim(candidates, 1000, Strategy::Largest);
+
+        assert!(!budget.is_met());
+        assert_eq!(names(budget.selected), vec!["large", "small"]);
+        assert!(budget.untouched.is_empty());
+    }
+}
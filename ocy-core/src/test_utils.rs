@@ -1,8 +1,13 @@
-use std::{ffi::OsString, path::Path};
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use eyre::ContextCompat;
 
-use crate::filesystem::FileSystem;
+use crate::filesystem::{FileSystem, FileSystemKind};
 use crate::models::{FileInfo, SimpleFileKind};
 
 pub struct MockFS {
@@ -17,51 +22,102 @@ impl MockFS {
 
 pub struct MockFSNode {
     name: OsString,
+    content: Vec<u8>,
+    modified: Option<SystemTime>,
     children: Vec<MockFSNode>,
+    /// Absolute path this node links to.
+    link: Option<PathBuf>,
+    /// Device and kind of the file system mounted on this node.
+    mount: Option<(u64, FileSystemKind)>,
 }
 
 impl MockFSNode {
     fn file_kind(&self) -> SimpleFileKind {
-        if self.children.is_empty() {
+        if self.link.is_some() {
+            SimpleFileKind::Symlink
+        } else if self.children.is_empty() {
             SimpleFileKind::File
         } else {
             SimpleFileKind::Directory
         }
     }
 
-    fn to_file_info(&self, parent: &Path) -> FileInfo {
-        let mut new_path = parent.to_path_buf();
+    fn to_file_info(&self, parent: &FileInfo) -> FileInfo {
+        let mut new_path = parent.path.clone();
         new_path.push(&self.name);
         FileInfo::new(
             new_path,
             self.name.to_string_lossy().to_string(),
             self.file_kind(),
         )
+        .with_modified(self.modified)
+        .with_device(self.mount.map(|(device, _)| device).or(parent.device))
     }
 }
 
 impl MockFSNode {
     pub fn file(name: &str) -> Self {
+        Self::file_with_content(name, "")
+    }
+
+    pub fn file_with_content(name: &str, content: &str) -> Self {
+        Self::file_with_bytes(name, content.as_bytes().to_vec())
+    }
+
+    pub fn file_with_bytes(name: &str, content: Vec<u8>) -> Self {
         MockFSNode {
             name: name.into(),
+            content,
+            modified: None,
             children: Vec::new(),
+            link: None,
+            mount: None,
         }
     }
 
     pub fn dir(name: &str, children: Vec<MockFSNode>) -> Self {
         MockFSNode {
             name: name.into(),
+            content: Vec::new(),
+            modified: None,
             children,
+            link: None,
+            mount: None,
+        }
+    }
+
+    pub fn symlink(name: &str, target: &str) -> Self {
+        MockFSNode {
+            link: Some(target.into()),
+            ..Self::file(name)
+        }
+    }
+
+    pub fn mounted(self, device: u64, kind: FileSystemKind) -> Self {
+        Self {
+            mount: Some((device, kind)),
+            ..self
+        }
+    }
+
+    pub fn modified(self, modified: SystemTime) -> Self {
+        Self {
+            modified: Some(modified),
+            ..self
         }
     }
 }
 
 impl MockFS {
+    /// Node at `path`, following links.
     fn get_node(&self, path: &Path) -> Option<&MockFSNode> {
         let mut current = &self.root;
 
         for c in path.iter().skip(1) {
             current = current.children.iter().find(|n| n.name == c)?;
+            if let Some(link) = &current.link {
+                current = self.get_node(link)?;
+            }
         }
         Some(current)
     }
@@ -73,7 +129,8 @@ impl FileSystem for MockFS {
             "/home/user".into(),
             "user".to_string(),
             SimpleFileKind::Directory,
-        ))
+        )
+        .with_device(Some(0)))
     }
 
     fn list_files(&self, file: &FileInfo) -> eyre::Result<Vec<FileInfo>> {
@@ -82,7 +139,7 @@ impl FileSystem for MockFS {
         let files = node
             .children
             .iter()
-            .map(|node| node.to_file_info(path))
+            .map(|node| node.to_file_info(file))
             .collect();
         Ok(files)
     }
@@ -90,4 +147,39 @@ impl FileSystem for MockFS {
     fn file_size(&self, _file: &FileInfo) -> eyre::Result<u64> {
         Ok(42)
     }
+
+    fn read_to_string(&self, file: &FileInfo) -> eyre::Result<String> {
+        let node = self
+            .get_node(&file.path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        Ok(String::from_utf8(node.content.clone())?)
+    }
+
+    fn read(&self, file: &FileInfo) -> eyre::Result<Vec<u8>> {
+        let node = self
+            .get_node(&file.path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        Ok(node.content.clone())
+    }
+
+    fn follow_symlink(&self, file: &FileInfo) -> eyre::Result<FileInfo> {
+        let node = self.get_node(&file.path).wrap_err("Cannot find node")?;
+        Ok(FileInfo::new(
+            file.path.clone(),
+            file.name.clone(),
+            node.file_kind(),
+        ))
+    }
+
+    fn file_id(&self, file: &FileInfo) -> Option<(u64, u64)> {
+        // Nodes do not move while walking, their address identifies them.
+        let node = self.get_node(&file.path)?;
+        Some((0, node as *const MockFSNode as u64))
+    }
+
+    fn file_system_kind(&self, dir: &FileInfo) -> FileSystemKind {
+        self.get_node(&dir.path)
+            .and_then(|node| node.mount)
+            .map_or(FileSystemKind::Local, |(_, kind)| kind)
+    }
 }
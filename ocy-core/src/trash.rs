@@ -0,0 +1,305 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, DirBuilder, OpenOptions},
+    io::{self, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Context, Result};
+
+use crate::{
+    filesystem::{available_space, data_home, move_path, FileSystemClean, RealFileSystem},
+    models::FileInfo,
+};
+
+const STICKY_BIT: u32 = 0o1000;
+
+/// Moves files to the freedesktop.org trash instead of deleting them.
+///
+/// Files on the same file system as the home trash go there. Files on other
+/// mounts go to the trash at the top of their mount (`$topdir/.Trash/$uid` or
+/// `$topdir/.Trash-$uid`), falling back to copying them to the home trash when
+/// neither is usable.
+pub struct TrashFileSystemClean {
+    home_trash: PathBuf,
+    uid: u32,
+    /// Measures what commands free.
+    sizes: RealFileSystem,
+}
+
+struct TrashDir {
+    root: PathBuf,
+    /// Mount point the trash belongs to, original paths are then stored
+    /// relative to it.
+    topdir: Option<PathBuf>,
+}
+
+impl TrashFileSystemClean {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_home_trash(data_home()?.join("Trash")))
+    }
+
+    pub fn with_home_trash(home_trash: PathBuf) -> Self {
+        // SAFETY: getuid cannot fail and has no side effect.
+        let uid = unsafe { libc::getuid() };
+        Self {
+            home_trash,
+            uid,
+            sizes: RealFileSystem::default(),
+        }
+    }
+
+    /// Measures what commands free with `sizes`, in the size mode of the
+    /// walk rather than in apparent sizes.
+    pub fn with_sizes(self, sizes: RealFileSystem) -> Self {
+        Self { sizes, ..self }
+    }
+
+    fn trash_dir_for(&self, path: &Path) -> Result<TrashDir> {
+        let device = fs::symlink_metadata(path)?.dev();
+        let home_trash = TrashDir {
+            root: self.home_trash.clone(),
+            topdir: None,
+        };
+
+        let home_device = self
+            .home_trash
+            .ancestors()
+            .find_map(|p| fs::metadata(p).ok())
+            .map(|m| m.dev());
+        if home_device == Some(device) {
+            return Ok(home_trash);
+        }
+
+        let topdir = mount_point(path, device);
+        Ok(self
+            .topdir_trash(&topdir)
+            .map(|root| TrashDir {
+                root,
+                topdir: Some(topdir),
+            })
+            .unwrap_or(home_trash))
+    }
+
+    fn topdir_trash(&self, topdir: &Path) -> Option<PathBuf> {
+        let admin_trash = topdir.join(".Trash");
+        if let Ok(metadata) = fs::symlink_metadata(&admin_trash) {
+            let sticky = metadata.permissions().mode() & STICKY_BIT != 0;
+            if metadata.is_dir() && sticky {
+                let user_trash = admin_trash.join(self.uid.to_string());
+                if ensure_private_dir(&user_trash).is_ok() {
+                    return Some(user_trash);
+                }
+            }
+        }
+
+        let user_trash = topdir.join(format!(".Trash-{}", self.uid));
+        ensure_private_dir(&user_trash).ok().map(|_| user_trash)
+    }
+}
+
+impl FileSystemClean for TrashFileSystemClean {
+    fn remove_file(&self, file: &FileInfo) -> Result<()> {
+        let trash = self.trash_dir_for(&file.path)?;
+        trash
+            .put(&file.path)
+            .wrap_err_with(|| format!("Cannot move to trash {}", trash.root.display()))
+    }
+
+    fn measure_size(&self, file: &FileInfo) -> Option<u64> {
+        self.sizes.measure_size(file)
+    }
+
+    fn moves_files(&self) -> bool {
+        true
+    }
+
+    fn available_space(&self, path: &Path) -> Option<u64> {
+        available_space(path)
+    }
+}
+
+impl TrashDir {
+    fn put(&self, path: &Path) -> Result<()> {
+        let files = self.root.join("files");
+        let info = self.root.join("info");
+        if let Some(parent) = self.root.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        ensure_private_dir(&self.root)?;
+        ensure_private_dir(&files)?;
+        ensure_private_dir(&info)?;
+
+        let name = path
+            .file_name()
+            .ok_or_else(|| eyre!("Cannot trash {}", path.display()))?;
+        let original_path = match &self.topdir {
+            Some(topdir) => path.strip_prefix(topdir).unwrap_or(path),
+            None => path,
+        };
+        let (trashed_name, info_file) = reserve_name(&files, &info, name, original_path)?;
+
+        if let Err(e) = move_path(path, &files.join(trashed_name)) {
+            let _ = fs::remove_file(info_file);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Finds a name free in both `files` and `info`, atomically claiming it by
+/// creating its `.trashinfo` file.
+fn reserve_name(
+    files: &Path,
+    info: &Path,
+    name: &OsStr,
+    original_path: &Path,
+) -> Result<(OsString, PathBuf)> {
+    let content = trash_info(original_path);
+
+    for n in 1.. {
+        let mut candidate = name.to_os_string();
+        if n > 1 {
+            candidate.push(format!(".{}", n));
+        }
+        if fs::symlink_metadata(files.join(&candidate)).is_ok() {
+            continue;
+        }
+
+        let mut info_name = candidate.clone();
+        info_name.push(".trashinfo");
+        let info_file = info.join(info_name);
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_file)
+        {
+            Ok(mut f) => {
+                f.write_all(content.as_bytes())?;
+                return Ok((candidate, info_file));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+fn trash_info(original_path: &Path) -> String {
+    format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(original_path.as_os_str().as_bytes()),
+        deletion_date()
+    )
+}
+
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Current local time, formatted as `YYYY-MM-DDThh:mm:ss`.
+fn deletion_date() -> String {
+    // SAFETY: `tm` is plain data fully initialized by localtime_r on success.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+}
+
+/// Topmost ancestor of `path` that is still on `device`.
+fn mount_point(path: &Path, device: u64) -> PathBuf {
+    let mut top = path;
+    for ancestor in path.ancestors().skip(1) {
+        match fs::metadata(ancestor) {
+            Ok(m) if m.dev() == device => top = ancestor,
+            _ => break,
+        }
+    }
+    top.to_path_buf()
+}
+
+/// Creates `dir` readable by its owner only when missing, and checks that it
+/// is a real directory otherwise.
+fn ensure_private_dir(dir: &Path) -> Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            if fs::symlink_metadata(dir)?.is_dir() {
+                Ok(())
+            } else {
+                Err(eyre!("{} is not a directory", dir.display()))
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        filesystem::FileSystemClean,
+        models::{FileInfo, SimpleFileKind},
+    };
+
+    use super::{percent_encode, TrashFileSystemClean};
+
+    #[test]
+    fn trashed_files_get_unique_names_and_info() -> eyre::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let trash = TrashFileSystemClean::with_home_trash(tmp.path().join("Trash"));
+
+        for project in ["a", "my project"] {
+            let target = tmp.path().join(project).join("target");
+            fs::create_dir_all(&target)?;
+            fs::write(target.join("out"), "x")?;
+            trash.remove_file(&FileInfo::new(
+                target.clone(),
+                "target".into(),
+                SimpleFileKind::Directory,
+            ))?;
+            assert!(!target.exists());
+        }
+
+        let files = tmp.path().join("Trash/files");
+        assert!(files.join("target/out").is_file());
+        assert!(files.join("target.2/out").is_file());
+
+        let info = fs::read_to_string(tmp.path().join("Trash/info/target.2.trashinfo"))?;
+        let expected_path = percent_encode(
+            tmp.path()
+                .join("my project/target")
+                .as_os_str()
+                .as_encoded_bytes(),
+        );
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains(&format!("Path={}\n", expected_path)));
+        assert!(expected_path.ends_with("/my%20project/target"));
+        assert!(info.contains("DeletionDate="));
+        Ok(())
+    }
+}
@@ -1,95 +1,440 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
 
 use crate::{
-    filesystem::FileSystem,
+    config::RulesConfig,
+    filesystem::{FileSystem, FileSystemKind},
+    git::{self, GitIndex, GIT_DIR},
+    gitignore::{IgnoreRules, GITIGNORE_FILE},
     matcher::{CleanStrategy, Matcher},
     models::RemovalCandidate,
     models::{FileInfo, SimpleFileKind},
+    scope::{RuleScope, LOCAL_RULES_FILE},
+    work_queue,
 };
 use eyre::Report;
 use eyre::Result;
 
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
 pub struct Walker<FS: FileSystem, N: WalkNotifier> {
     fs: FS,
-    matchers: Vec<Matcher>,
+    rules: Arc<RuleScope>,
     notifier: N,
-    ignores: HashSet<PathBuf>,
-    walk_all: bool,
+    options: WalkOptions,
+    /// Projects modified after this are left alone.
+    modified_before: Option<SystemTime>,
+    /// Candidates waiting for their size while walking with deferred sizes.
+    sizing: Mutex<Option<Sender<RemovalCandidate>>>,
+    /// Directories walked so far when following symbolic links.
+    visited: Mutex<HashSet<(u64, u64)>>,
+    /// Targets of the candidates notified so far, which are not walked into
+    /// and which no other candidate may overlap.
+    claimed: Mutex<BTreeSet<PathBuf>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WalkOptions {
+    pub ignores: HashSet<PathBuf>,
+    /// Walk into hidden directories too.
+    pub walk_all: bool,
+    /// Only propose candidates of projects whose files were not modified
+    /// for this long.
+    pub older_than: Option<Duration>,
+    /// Drop candidates smaller than this many bytes, or of unknown size.
+    pub min_size: Option<u64>,
+    /// Record when projects were last modified on their candidates.
+    pub project_age: bool,
+    /// Threads walking directories concurrently, `0` or `1` to walk
+    /// sequentially. Candidates are then notified in no particular order.
+    pub threads: usize,
+    /// Notify candidates as soon as they are found, with an unknown size,
+    /// and compute sizes on `threads` other threads. Ignored with
+    /// `min_size`, which needs sizes to filter candidates.
+    pub deferred_sizes: bool,
+    /// Walk into symbolic links to directories, each directory once at
+    /// most. Sizes still leave out what links point to.
+    pub follow_symlinks: bool,
+    /// Stay on the file system of the directory walked from, like `find -xdev`.
+    pub one_file_system: bool,
+    /// Count the files tracked by git under candidates in a git work tree.
+    /// Work trees are always looked for when a rule removes what git
+    /// ignores.
+    pub check_git: bool,
+}
+
+/// Why the walk left a directory or a candidate alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "snake_case"))]
+pub enum SkipReason {
+    /// On another file system than the walk started from.
+    OtherFileSystem,
+    PseudoFileSystem,
+    NetworkFileSystem,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::OtherFileSystem => "on another file system",
+            SkipReason::PseudoFileSystem => "pseudo file system",
+            SkipReason::NetworkFileSystem => "network file system",
+        })
+    }
 }
 
-pub trait WalkNotifier {
+/// Notifications may come from several threads when walking in parallel.
+pub trait WalkNotifier: Sync {
     fn notify_entered_directory(&self, dir: &FileInfo);
     fn notify_candidate_for_removal(&self, candidate: RemovalCandidate);
+    /// A candidate notified earlier, now with its size, when walking with
+    /// deferred sizes.
+    fn notify_candidate_size_computed(&self, candidate: RemovalCandidate);
     fn notify_fail_to_scan(&self, e: &FileInfo, report: Report);
+    fn notify_skipped(&self, file: &FileInfo, reason: SkipReason);
     fn notify_walk_finish(&self);
 }
 
 impl<FS: FileSystem, N: WalkNotifier> Walker<FS, N> {
-    pub fn new(
-        fs: FS,
-        matchers: Vec<Matcher>,
-        notifier: N,
-        ignores: HashSet<PathBuf>,
-        walk_all: bool,
-    ) -> Self {
+    pub fn new(fs: FS, matchers: Vec<Matcher>, notifier: N, options: WalkOptions) -> Self {
+        let modified_before = options
+            .older_than
+            .and_then(|age| SystemTime::now().checked_sub(age));
         Self {
             fs,
-            matchers,
+            rules: RuleScope::root(matchers),
             notifier,
-            ignores,
-            walk_all,
+            options,
+            modified_before,
+            sizing: Mutex::default(),
+            visited: Mutex::default(),
+            claimed: Mutex::default(),
         }
     }
 
+    /// Walks `path`, only notifying the end of the walk once the sizes of
+    /// all candidates are known.
     pub fn walk_from_path(&self, path: &FileInfo) {
-        self.process_dir(path);
+        if self.defers_sizes() {
+            let (tx, rx) = mpsc::channel();
+            let rx = Mutex::new(rx);
+            thread::scope(|scope| {
+                for _ in 0..self.options.threads.max(1) {
+                    scope.spawn(|| self.compute_sizes(&rx));
+                }
+                *self.sizing.lock().unwrap() = Some(tx);
+                self.walk(path);
+                // Closes the channel, sizing threads stop once it is drained.
+                self.sizing.lock().unwrap().take();
+            });
+        } else {
+            self.walk(path);
+        }
         self.notifier.notify_walk_finish();
     }
 
-    fn process_dir(&self, file: &FileInfo) {
-        if self.ignores.contains(&file.path) {
-            return;
+    fn walk(&self, path: &FileInfo) {
+        let rules = self.enclosing_rules(path);
+        if self.options.threads > 1 {
+            self.walk_parallel(path, rules);
+        } else {
+            self.process_dir(path, &rules);
+        }
+    }
+
+    /// Rules for `path`, in the git work tree it is part of, if any, along
+    /// with the `.gitignore` files of the directories above it.
+    fn enclosing_rules(&self, path: &FileInfo) -> Arc<RuleScope> {
+        if !self.reads_git(&self.rules) {
+            return self.rules.clone();
         }
-        match self.process_entries(file) {
-            Ok(children) => {
-                children.iter().for_each(|d| self.process_dir(d));
+        let mut between: Vec<(FileInfo, Vec<FileInfo>)> = Vec::new();
+        for ancestor in path.path.ancestors().skip(1) {
+            let dir = FileInfo::new(ancestor.to_path_buf(), "".into(), SimpleFileKind::Directory);
+            let Ok(entries) = self.fs.list_files(&dir) else {
+                continue;
+            };
+            if entries.iter().any(|e| e.name == GIT_DIR) {
+                let mut rules = self.git_rules(&dir, &entries, &self.rules);
+                rules = self.ignore_rules(&dir, &entries, &rules);
+                for (dir, entries) in between.iter().rev() {
+                    rules = self.ignore_rules(dir, entries, &rules);
+                }
+                return rules;
             }
-            Err(report) => self.notifier.notify_fail_to_scan(file, report),
+            between.push((dir, entries));
         }
+        self.rules.clone()
     }
 
-    fn process_entries(&self, file: &FileInfo) -> Result<Vec<FileInfo>> {
+    fn reads_git(&self, rules: &RuleScope) -> bool {
+        self.options.check_git || rules.removes_ignored()
+    }
+
+    fn defers_sizes(&self) -> bool {
+        self.options.deferred_sizes && self.options.min_size.is_none()
+    }
+
+    fn compute_sizes(&self, candidates: &Mutex<Receiver<RemovalCandidate>>) {
+        loop {
+            let next = candidates.lock().unwrap().recv();
+            let Ok(candidate) = next else {
+                return;
+            };
+            let size = self.fs.file_size(candidate.target()).ok();
+            self.notifier
+                .notify_candidate_size_computed(candidate.with_file_size(size));
+        }
+    }
+
+    fn process_dir(&self, file: &FileInfo, rules: &Arc<RuleScope>) {
+        for (child, rules) in self.process_walkable_dir(file, rules) {
+            self.process_dir(&child, &rules);
+        }
+    }
+
+    fn walk_parallel(&self, path: &FileInfo, rules: Arc<RuleScope>) {
+        let root = (path.clone(), rules);
+        work_queue::run(self.options.threads, vec![root], |(dir, rules), spawned| {
+            spawned.extend(self.process_walkable_dir(&dir, &rules));
+        });
+    }
+
+    /// Processes `file` unless ignored, returning the subdirectories to walk
+    /// along with their rules.
+    fn process_walkable_dir(
+        &self,
+        file: &FileInfo,
+        rules: &Arc<RuleScope>,
+    ) -> Vec<(FileInfo, Arc<RuleScope>)> {
+        if self.options.ignores.contains(&file.path) || !self.first_visit(file) {
+            return Vec::new();
+        }
+        match self.process_entries(file, rules) {
+            Ok((children, rules)) => children
+                .into_iter()
+                .map(|child| (child, rules.clone()))
+                .collect(),
+            Err(report) => {
+                self.notifier.notify_fail_to_scan(file, report);
+                Vec::new()
+            }
+        }
+    }
+
+    fn process_entries(
+        &self,
+        file: &FileInfo,
+        rules: &Arc<RuleScope>,
+    ) -> Result<(Vec<FileInfo>, Arc<RuleScope>)> {
         self.notifier.notify_entered_directory(file);
         let mut entries = self.fs.list_files(file)?;
+        self.drop_claimed(&mut entries);
+        let rules = self.local_rules(file, &entries, rules);
+        let rules = self.git_rules(file, &entries, &rules);
+        let rules = self.ignore_rules(file, &entries, &rules);
 
-        for matcher in &self.matchers {
-            entries = self.process_matcher(file, matcher, entries);
+        for matcher in rules.matchers() {
+            entries = self.process_matcher(file, matcher, &rules, entries);
+        }
+        if self.options.follow_symlinks {
+            entries = entries
+                .into_iter()
+                .map(|f| self.follow_symlink(f))
+                .collect();
+        }
+        entries.retain(|f| self.is_walkable(f) && !self.is_skipped(file, f));
+        Ok((entries, rules))
+    }
+
+    /// Whether `file`, found in `dir`, is left alone because of the file
+    /// system it is on, notifying it then. Only checked when crossing into
+    /// another file system.
+    fn is_skipped(&self, dir: &FileInfo, file: &FileInfo) -> bool {
+        match self.skip_reason(dir, file) {
+            Some(reason) => {
+                self.notifier.notify_skipped(file, reason);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn skip_reason(&self, dir: &FileInfo, file: &FileInfo) -> Option<SkipReason> {
+        let crossing = matches!((dir.device, file.device), (Some(a), Some(b)) if a != b);
+        if !crossing {
+            return None;
+        }
+        if self.options.one_file_system {
+            return Some(SkipReason::OtherFileSystem);
+        }
+        match self.fs.file_system_kind(file) {
+            FileSystemKind::Local => None,
+            FileSystemKind::Pseudo => Some(SkipReason::PseudoFileSystem),
+            FileSystemKind::Network => Some(SkipReason::NetworkFileSystem),
+        }
+    }
+
+    /// Whether `dir` is walked for the first time. Only tracked when
+    /// following symbolic links, which may lead to a directory twice or
+    /// into cycles.
+    fn first_visit(&self, dir: &FileInfo) -> bool {
+        if !self.options.follow_symlinks {
+            return true;
+        }
+        match self.fs.file_id(dir) {
+            Some(id) => self.visited.lock().unwrap().insert(id),
+            None => true,
+        }
+    }
+
+    /// What `file` points to when it is a symbolic link, dangling links are
+    /// kept as they are.
+    fn follow_symlink(&self, file: FileInfo) -> FileInfo {
+        if file.kind != SimpleFileKind::Symlink {
+            return file;
+        }
+        self.fs.follow_symlink(&file).unwrap_or(file)
+    }
+
+    /// Rules for `dir`, nesting those of its `.ocy.toml` when it has one.
+    fn local_rules(
+        &self,
+        dir: &FileInfo,
+        entries: &[FileInfo],
+        rules: &Arc<RuleScope>,
+    ) -> Arc<RuleScope> {
+        let config_file = entries
+            .iter()
+            .find(|e| e.kind == SimpleFileKind::File && e.name == LOCAL_RULES_FILE);
+
+        let Some(config_file) = config_file else {
+            return rules.clone();
+        };
+
+        let config = self
+            .fs
+            .read_to_string(config_file)
+            .and_then(|source| RulesConfig::parse_local(&source));
+
+        match config {
+            Ok(config) => rules.nested(dir.path.clone(), config),
+            Err(report) => {
+                self.notifier.notify_fail_to_scan(config_file, report);
+                rules.clone()
+            }
+        }
+    }
+
+    /// Rules for `dir`, nesting the index of its git work tree when it is the
+    /// root of one.
+    fn git_rules(
+        &self,
+        dir: &FileInfo,
+        entries: &[FileInfo],
+        rules: &Arc<RuleScope>,
+    ) -> Arc<RuleScope> {
+        if !self.reads_git(rules) {
+            return rules.clone();
+        }
+        let Some(dot_git) = entries.iter().find(|e| e.name == GIT_DIR) else {
+            return rules.clone();
+        };
+
+        let scope = git::git_dir(&self.fs, &dir.path, dot_git).and_then(|git_dir| {
+            let git_index = GitIndex::read(&self.fs, &dir.path, &git_dir)?;
+            let excluded = if rules.removes_ignored() {
+                Some(git::read_exclude(&self.fs, &dir.path, &git_dir)?)
+            } else {
+                None
+            };
+            Ok(rules.with_git_index(dir.path.clone(), git_index, excluded))
+        });
+        match scope {
+            Ok(scope) => scope,
+            Err(report) => {
+                self.notifier.notify_fail_to_scan(dot_git, report);
+                rules.clone()
+            }
+        }
+    }
+
+    /// Rules for `dir`, nesting its `.gitignore` when it has one and a rule
+    /// removes what git ignores.
+    fn ignore_rules(
+        &self,
+        dir: &FileInfo,
+        entries: &[FileInfo],
+        rules: &Arc<RuleScope>,
+    ) -> Arc<RuleScope> {
+        if !rules.removes_ignored() {
+            return rules.clone();
+        }
+        let ignore_file = entries
+            .iter()
+            .find(|e| e.kind == SimpleFileKind::File && e.name == GITIGNORE_FILE);
+        let Some(ignore_file) = ignore_file else {
+            return rules.clone();
+        };
+
+        match self.fs.read_to_string(ignore_file) {
+            Ok(source) => rules.with_ignore_rules(
+                dir.path.clone(),
+                IgnoreRules::parse(dir.path.clone(), &source),
+            ),
+            Err(report) => {
+                self.notifier.notify_fail_to_scan(ignore_file, report);
+                rules.clone()
+            }
         }
-        entries.retain(|f| self.is_walkable(f));
-        Ok(entries)
     }
 
     fn process_matcher(
         &self,
         work_dir: &FileInfo,
         matcher: &Matcher,
+        rules: &Arc<RuleScope>,
         entries: Vec<FileInfo>,
     ) -> Vec<FileInfo> {
-        if matcher.any_entry_match(&entries) {
+        if matcher.matches_project(&self.fs, work_dir, &entries) {
             match &matcher.clean_strategy {
                 CleanStrategy::Remove(pattern) => {
-                    let (mut to_remove, remaining) = pattern.find_files_to_remove(entries);
-                    to_remove.retain(|p| !self.ignores.contains(&p.path));
-                    self.notify_removal_candidates(matcher, to_remove);
-                    remaining
+                    let list = self.removal_lister(work_dir, rules);
+                    let (to_remove, remaining) = pattern.find_files_to_remove(entries, list);
+                    self.process_removals(work_dir, matcher, rules, to_remove, remaining)
+                }
+                CleanStrategy::RemoveIgnored(exclusions) => {
+                    let (to_remove, remaining) = entries.into_iter().partition(|e| {
+                        e.kind == SimpleFileKind::Directory
+                            && !exclusions.excludes(e)
+                            && rules.is_ignored(&e.path, true)
+                    });
+                    self.process_removals(work_dir, matcher, rules, to_remove, remaining)
                 }
                 CleanStrategy::RunCommand(cmd) => {
-                    let candidate = RemovalCandidate::new_cmd(
-                        matcher.name.clone(),
-                        work_dir.clone(),
-                        cmd.clone(),
-                    );
-                    self.notifier.notify_candidate_for_removal(candidate);
+                    // The effect of a command cannot be measured beforehand.
+                    if self.options.min_size.is_some() {
+                        return entries;
+                    }
+                    if let Some(project_modified) = self.project_age(&entries, &[]) {
+                        let candidate = RemovalCandidate::new_cmd(
+                            matcher.name.clone(),
+                            work_dir.clone(),
+                            cmd.clone(),
+                        )
+                        .with_project_modified(project_modified);
+                        self.notifier.notify_candidate_for_removal(candidate);
+                    }
                     entries
                 }
             }
@@ -98,54 +443,274 @@ impl<FS: FileSystem, N: WalkNotifier> Walker<FS, N> {
         }
     }
 
-    fn notify_removal_candidates(&self, matcher: &Matcher, to_remove: Vec<FileInfo>) {
+    /// Lists the directories below `work_dir` a removal pattern looks into,
+    /// as the walk would: ignored directories, hidden ones unless named,
+    /// directories on other file systems and directories already listed
+    /// through a symbolic link are not entered, and the paths the `.ocy.toml`
+    /// files met on the way protect are left out.
+    fn removal_lister<'a>(
+        &'a self,
+        work_dir: &FileInfo,
+        rules: &Arc<RuleScope>,
+    ) -> impl FnMut(&FileInfo, bool) -> Vec<FileInfo> + 'a {
+        let mut scopes =
+            HashMap::from([(work_dir.path.clone(), (work_dir.clone(), rules.clone()))]);
+        let mut visited = HashSet::new();
+        move |dir: &FileInfo, named_hidden: bool| {
+            let Some((parent, rules)) = dir.path.parent().and_then(|p| scopes.get(p)).cloned()
+            else {
+                return Vec::new();
+            };
+            let dir = if self.options.follow_symlinks {
+                self.follow_symlink(dir.clone())
+            } else {
+                dir.clone()
+            };
+            let walkable = if named_hidden {
+                dir.kind == SimpleFileKind::Directory
+            } else {
+                self.is_walkable(&dir)
+            };
+            if self.options.ignores.contains(&dir.path)
+                || !walkable
+                || self.skip_reason(&parent, &dir).is_some()
+            {
+                return Vec::new();
+            }
+            if self.options.follow_symlinks {
+                if let Some(id) = self.fs.file_id(&dir) {
+                    if !visited.insert(id) {
+                        return Vec::new();
+                    }
+                }
+            }
+            let mut entries = match self.fs.list_files(&dir) {
+                Ok(entries) => entries,
+                Err(report) => {
+                    self.notifier.notify_fail_to_scan(&dir, report);
+                    return Vec::new();
+                }
+            };
+            let rules = self.local_rules(&dir, &entries, &rules);
+            entries.retain(|e| !rules.is_protected(&e.path));
+            scopes.insert(dir.path.clone(), (dir, rules));
+            entries
+        }
+    }
+
+    /// Notifies the candidates among `to_remove`, returning the entries left
+    /// to the next rules and to walk into.
+    fn process_removals(
+        &self,
+        work_dir: &FileInfo,
+        matcher: &Matcher,
+        rules: &RuleScope,
+        to_remove: Vec<FileInfo>,
+        mut remaining: Vec<FileInfo>,
+    ) -> Vec<FileInfo> {
+        let (protected, to_remove): (Vec<_>, Vec<_>) = to_remove
+            .into_iter()
+            .filter(|p| !self.options.ignores.contains(&p.path))
+            .filter(|p| !self.is_skipped(work_dir, p))
+            .partition(|p| rules.is_protected(&p.path));
+        // Protected paths nested deeper are walked into from their parent.
+        remaining.extend(
+            protected
+                .into_iter()
+                .filter(|p| p.path.parent() == Some(&work_dir.path)),
+        );
+        if !to_remove.is_empty() {
+            if let Some(project_modified) = self.project_age(&remaining, &to_remove) {
+                self.notify_removal_candidates(matcher, rules, to_remove, project_modified);
+            }
+        }
+        remaining
+    }
+
+    fn notify_removal_candidates(
+        &self,
+        matcher: &Matcher,
+        rules: &RuleScope,
+        to_remove: Vec<FileInfo>,
+        project_modified: Option<SystemTime>,
+    ) {
         to_remove
             .into_iter()
-            .map(|f| self.removal_candidate(matcher, f))
-            .for_each(|c| self.notifier.notify_candidate_for_removal(c));
+            .filter(|f| self.claim(&f.path))
+            .map(|f| {
+                let tracked_files = rules.tracked_files(&f.path);
+                self.removal_candidate(matcher, f)
+                    .with_project_modified(project_modified)
+                    .with_tracked_files(tracked_files)
+            })
+            .filter(|c| self.is_large_enough(c))
+            .for_each(|c| self.notify_candidate(c));
+    }
+
+    /// Claims `path` for a candidate, unless it overlaps one claimed already.
+    fn claim(&self, path: &Path) -> bool {
+        let mut claimed = self.claimed.lock().unwrap();
+        let in_claimed = path.ancestors().any(|a| claimed.contains(a));
+        // Paths under `path` sort right after it
+        let holds_claimed = claimed
+            .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+            .next()
+            .is_some_and(|next| next.starts_with(path));
+        if in_claimed || holds_claimed {
+            return false;
+        }
+        claimed.insert(path.to_path_buf())
+    }
+
+    /// Leaves out the entries claimed by candidates found from above them.
+    fn drop_claimed(&self, entries: &mut Vec<FileInfo>) {
+        let claimed = self.claimed.lock().unwrap();
+        if !claimed.is_empty() {
+            entries.retain(|e| !claimed.contains(&e.path));
+        }
+    }
+
+    fn notify_candidate(&self, candidate: RemovalCandidate) {
+        let sizing = self.sizing.lock().unwrap().clone();
+        match sizing {
+            Some(sizing) => {
+                self.notifier
+                    .notify_candidate_for_removal(candidate.clone());
+                let _ = sizing.send(candidate);
+            }
+            None => self.notifier.notify_candidate_for_removal(candidate),
+        }
     }
 
     fn removal_candidate(&self, matcher: &Matcher, file: FileInfo) -> RemovalCandidate {
-        let size = self.fs.file_size(&file).ok();
+        let size = if self.defers_sizes() {
+            None
+        } else {
+            self.fs.file_size(&file).ok()
+        };
         RemovalCandidate::new(matcher.name.clone(), file, size)
     }
 
+    fn is_large_enough(&self, candidate: &RemovalCandidate) -> bool {
+        match self.options.min_size {
+            Some(min_size) => candidate.file_size().is_some_and(|size| size >= min_size),
+            None => true,
+        }
+    }
+
+    /// Last modification time of a project made of `entries`, with the
+    /// removal targets nested in them in `excluded`, when it is needed.
+    /// Returns `None` when the project was modified too recently, or when its
+    /// age is unknown but required.
+    fn project_age(
+        &self,
+        entries: &[FileInfo],
+        excluded: &[FileInfo],
+    ) -> Option<Option<SystemTime>> {
+        if self.modified_before.is_none() && !self.options.project_age {
+            return Some(None);
+        }
+        let modified = self.last_modified(entries, excluded, self.modified_before);
+        match self.modified_before {
+            Some(modified_before) => modified
+                .filter(|modified| *modified < modified_before)
+                .map(Some),
+            None => Some(modified),
+        }
+    }
+
+    /// Latest modification time of the files among `entries` and their
+    /// non-hidden subdirectories, `excluded` left out. Stops looking once a
+    /// file modified at or after `recent` is found, as the project is then
+    /// recent whatever else it holds.
+    fn last_modified(
+        &self,
+        entries: &[FileInfo],
+        excluded: &[FileInfo],
+        recent: Option<SystemTime>,
+    ) -> Option<SystemTime> {
+        let mut latest = None;
+        let mut listings = vec![entries.to_vec()];
+        while let Some(entries) = listings.pop() {
+            for entry in entries {
+                if excluded.iter().any(|e| e.path == entry.path) {
+                    continue;
+                }
+                match entry.kind {
+                    SimpleFileKind::File | SimpleFileKind::Symlink => {
+                        latest = latest.max(entry.modified);
+                        if recent.is_some_and(|recent| latest >= Some(recent)) {
+                            return latest;
+                        }
+                    }
+                    SimpleFileKind::Directory if entry.name.starts_with('.') => {}
+                    SimpleFileKind::Directory => {
+                        if let Ok(children) = self.fs.list_files(&entry) {
+                            listings.push(children);
+                        }
+                    }
+                }
+            }
+        }
+        latest
+    }
+
     fn is_walkable(&self, file: &FileInfo) -> bool {
-        file.kind == SimpleFileKind::Directory && (self.walk_all || !file.name.starts_with('.'))
+        file.kind == SimpleFileKind::Directory
+            && (self.options.walk_all || !file.name.starts_with('.'))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, collections::HashSet, path::PathBuf, str::FromStr};
+    use std::{
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::Mutex,
+        time::{Duration, SystemTime},
+    };
 
     use glob::Pattern;
 
     use crate::{
-        filesystem::FileSystem,
+        filesystem::{FileSystem, FileSystemKind},
+        git::tests::encode_index,
         matcher::Matcher,
-        models::FileInfo,
+        models::{FileInfo, SimpleFileKind},
         test_utils::{MockFS, MockFSNode},
-        walker::Walker,
+        walker::{WalkOptions, Walker},
     };
 
-    use super::WalkNotifier;
+    use super::{SkipReason, WalkNotifier};
     use crate::models::{RemovalAction, RemovalCandidate};
 
     #[derive(Debug, Default)]
     struct VecWalkNotifier {
-        pub to_remove: RefCell<Vec<RemovalCandidate>>,
+        pub to_remove: Mutex<Vec<RemovalCandidate>>,
+        pub sized: Mutex<Vec<RemovalCandidate>>,
+        pub skipped: Mutex<Vec<(PathBuf, SkipReason)>>,
     }
 
     impl WalkNotifier for &VecWalkNotifier {
         fn notify_entered_directory(&self, _dir: &FileInfo) {}
 
         fn notify_candidate_for_removal(&self, candidate: RemovalCandidate) {
-            self.to_remove.borrow_mut().push(candidate);
+            self.to_remove.lock().unwrap().push(candidate);
+        }
+
+        fn notify_candidate_size_computed(&self, candidate: RemovalCandidate) {
+            self.sized.lock().unwrap().push(candidate);
         }
 
         fn notify_fail_to_scan(&self, _e: &FileInfo, _report: eyre::Error) {}
 
+        fn notify_skipped(&self, file: &FileInfo, reason: SkipReason) {
+            self.skipped
+                .lock()
+                .unwrap()
+                .push((file.path.clone(), reason));
+        }
+
         fn notify_walk_finish(&self) {}
     }
 
@@ -179,14 +744,13 @@ mod tests {
                 "Cargo".into(),
                 Pattern::new("Cargo.toml")?,
                 Pattern::new("target")?,
-            )],
+            )?],
             &notifier,
-            HashSet::new(),
-            false,
+            WalkOptions::default(),
         );
         walker.walk_from_path(&current_dir);
 
-        let to_remove = notifier.to_remove.into_inner();
+        let to_remove = notifier.to_remove.into_inner().unwrap();
 
         assert_eq!(1, to_remove.len());
         let c = to_remove.into_iter().next().unwrap();
@@ -199,11 +763,802 @@ mod tests {
                     PathBuf::from_str("/home/user/projectA/target").unwrap()
                 )
             }
-            RemovalAction::RunCommand { work_dir, command } => {
+            RemovalAction::RunCommand { .. } => {
                 panic!("should be delete")
             }
         }
 
         Ok(())
     }
+
+    #[test]
+    fn deferred_sizes_are_notified_after_candidates() -> eyre::Result<()> {
+        let fs = setup_mock_fs();
+        let current_dir = fs.current_directory()?;
+        let notifier = VecWalkNotifier::default();
+        let walker = Walker::new(
+            fs,
+            vec![Matcher::with_remove_strategy(
+                "Cargo".into(),
+                Pattern::new("Cargo.toml")?,
+                Pattern::new("target")?,
+            )?],
+            &notifier,
+            WalkOptions {
+                deferred_sizes: true,
+                ..WalkOptions::default()
+            },
+        );
+        walker.walk_from_path(&current_dir);
+
+        let to_remove = notifier.to_remove.into_inner().unwrap();
+        let sized = notifier.sized.into_inner().unwrap();
+        assert_eq!(to_remove.len(), 1);
+        assert_eq!(to_remove[0].file_size(), None);
+        assert_eq!(sized.len(), 1);
+        assert_eq!(sized[0].target().path, to_remove[0].target().path);
+        assert_eq!(sized[0].file_size(), Some(42));
+
+        Ok(())
+    }
+
+    #[test]
+    fn local_rules_apply_to_their_subtree() -> eyre::Result<()> {
+        let gradle_project = |name: &str| {
+            MockFSNode::dir(
+                name,
+                vec![
+                    MockFSNode::file("build.gradle"),
+                    MockFSNode::dir("build", vec![MockFSNode::file("out.class")]),
+                ],
+            )
+        };
+        // Walking in parallel must not change the outcome
+        for threads in [1, 4] {
+            let fs = MockFS::new(MockFSNode::dir(
+                "/",
+                vec![MockFSNode::dir(
+                    "home",
+                    vec![MockFSNode::dir(
+                        "user",
+                        vec![
+                            MockFSNode::dir(
+                                "mono",
+                                vec![
+                                    MockFSNode::file_with_content(
+                                        ".ocy.toml",
+                                        r#"protect = ["legacy/build"]"#,
+                                    ),
+                                    gradle_project("app"),
+                                    gradle_project("legacy"),
+                                    MockFSNode::dir(
+                                        "tools",
+                                        vec![
+                                            MockFSNode::file_with_content(
+                                                ".ocy.toml",
+                                                r#"
+                                                disable = ["Gradle"]
+
+                                                [[rule]]
+                                                name = "Bazel"
+                                                marker = "WORKSPACE"
+                                                remove = "bazel-out"
+                                                "#,
+                                            ),
+                                            MockFSNode::file("WORKSPACE"),
+                                            MockFSNode::file("bazel-out"),
+                                            gradle_project("legacy"),
+                                        ],
+                                    ),
+                                ],
+                            ),
+                            gradle_project("legacy"),
+                        ],
+                    )],
+                )],
+            ));
+            let current_dir = fs.current_directory()?;
+            let notifier = VecWalkNotifier::default();
+            let walker = Walker::new(
+                fs,
+                vec![Matcher::with_remove_strategy(
+                    "Gradle".into(),
+                    Pattern::new("build.gradle")?,
+                    Pattern::new("build")?,
+                )?],
+                &notifier,
+                WalkOptions {
+                    threads,
+                    ..WalkOptions::default()
+                },
+            );
+            walker.walk_from_path(&current_dir);
+
+            let mut found: Vec<_> = notifier
+                .to_remove
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|c| match c.action {
+                    RemovalAction::Delete { file_info, .. } => (c.matcher_name, file_info.path),
+                    RemovalAction::RunCommand { .. } => panic!("should be delete"),
+                })
+                .collect();
+            found.sort();
+
+            assert_eq!(
+                found,
+                vec![
+                    (
+                        "Bazel".into(),
+                        PathBuf::from("/home/user/mono/tools/bazel-out")
+                    ),
+                    ("Gradle".into(), PathBuf::from("/home/user/legacy/build")),
+                    ("Gradle".into(), PathBuf::from("/home/user/mono/app/build")),
+                ]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn follows_symlinks_once_when_asked() -> eyre::Result<()> {
+        let project = |name: &str| {
+            MockFSNode::dir(
+                name,
+                vec![MockFSNode::file("Cargo.toml"), MockFSNode::file("target")],
+            )
+        };
+        for follow_symlinks in [false, true] {
+            let fs = MockFS::new(MockFSNode::dir(
+                "/",
+                vec![MockFSNode::dir(
+                    "home",
+                    vec![MockFSNode::dir(
+                        "user",
+                        vec![
+                            MockFSNode::dir(
+                                "work",
+                                vec![
+                                    project("own"),
+                                    MockFSNode::symlink("shared", "/home/user/elsewhere/shared"),
+                                    MockFSNode::symlink("again", "/home/user/work/own"),
+                                    MockFSNode::symlink("loop", "/home/user/work"),
+                                ],
+                            ),
+                            MockFSNode::dir("elsewhere", vec![project("shared")]),
+                        ],
+                    )],
+                )],
+            ));
+            let work = FileInfo::new(
+                "/home/user/work".into(),
+                "work".into(),
+                SimpleFileKind::Directory,
+            );
+            let notifier = VecWalkNotifier::default();
+            let walker = Walker::new(
+                fs,
+                vec![Matcher::with_remove_strategy(
+                    "Cargo".into(),
+                    Pattern::new("Cargo.toml")?,
+                    Pattern::new("target")?,
+                )?],
+                &notifier,
+                WalkOptions {
+                    follow_symlinks,
+                    ..WalkOptions::default()
+                },
+            );
+            walker.walk_from_path(&work);
+
+            let found: Vec<_> = notifier
+                .to_remove
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|c| c.target().path.clone())
+                .collect();
+            let mut expected = vec![PathBuf::from("/home/user/work/own/target")];
+            if follow_symlinks {
+                expected.push(PathBuf::from("/home/user/work/shared/target"));
+            }
+            assert_eq!(found, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_other_file_systems() -> eyre::Result<()> {
+        let project = |name: &str| {
+            MockFSNode::dir(
+                name,
+                vec![MockFSNode::file("Cargo.toml"), MockFSNode::file("target")],
+            )
+        };
+        for one_file_system in [false, true] {
+            let fs = MockFS::new(MockFSNode::dir(
+                "/",
+                vec![MockFSNode::dir(
+                    "home",
+                    vec![MockFSNode::dir(
+                        "user",
+                        vec![
+                            project("local"),
+                            MockFSNode::dir("usb", vec![project("app")])
+                                .mounted(1, FileSystemKind::Local),
+                            MockFSNode::dir("nfs", vec![project("shared")])
+                                .mounted(2, FileSystemKind::Network),
+                            MockFSNode::dir("proc", vec![project("self")])
+                                .mounted(3, FileSystemKind::Pseudo),
+                        ],
+                    )],
+                )],
+            ));
+            let current_dir = fs.current_directory()?;
+            let notifier = VecWalkNotifier::default();
+            let walker = Walker::new(
+                fs,
+                vec![Matcher::with_remove_strategy(
+                    "Cargo".into(),
+                    Pattern::new("Cargo.toml")?,
+                    Pattern::new("target")?,
+                )?],
+                &notifier,
+                WalkOptions {
+                    one_file_system,
+                    ..WalkOptions::default()
+                },
+            );
+            walker.walk_from_path(&current_dir);
+
+            let found: Vec<_> = notifier
+                .to_remove
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|c| c.target().path.clone())
+                .collect();
+            let skipped = notifier.skipped.into_inner().unwrap();
+            let path = PathBuf::from;
+            if one_file_system {
+                assert_eq!(found, vec![path("/home/user/local/target")]);
+                assert_eq!(
+                    skipped,
+                    vec![
+                        (path("/home/user/usb"), SkipReason::OtherFileSystem),
+                        (path("/home/user/nfs"), SkipReason::OtherFileSystem),
+                        (path("/home/user/proc"), SkipReason::OtherFileSystem),
+                    ]
+                );
+            } else {
+                assert_eq!(
+                    found,
+                    vec![
+                        path("/home/user/local/target"),
+                        path("/home/user/usb/app/target"),
+                    ]
+                );
+                assert_eq!(
+                    skipped,
+                    vec![
+                        (path("/home/user/nfs"), SkipReason::NetworkFileSystem),
+                        (path("/home/user/proc"), SkipReason::PseudoFileSystem),
+                    ]
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn counts_files_tracked_by_git() -> eyre::Result<()> {
+        let index = encode_index(
+            2,
+            &["app/build/gen.java", "app/build.gradle", "lib/build.gradle"],
+        );
+        let gradle_project = |name: &str, output: &str| {
+            MockFSNode::dir(
+                name,
+                vec![
+                    MockFSNode::file("build.gradle"),
+                    MockFSNode::dir("build", vec![MockFSNode::file(output)]),
+                ],
+            )
+        };
+        let setup_fs = || {
+            MockFS::new(MockFSNode::dir(
+                "/",
+                vec![MockFSNode::dir(
+                    "home",
+                    vec![MockFSNode::dir(
+                        "user",
+                        vec![MockFSNode::dir(
+                            "repo",
+                            vec![
+                                MockFSNode::dir(
+                                    ".git",
+                                    vec![MockFSNode::file_with_bytes("index", index.clone())],
+                                ),
+                                gradle_project("app", "gen.java"),
+                                gradle_project("lib", "out.class"),
+                            ],
+                        )],
+                    )],
+                )],
+            ))
+        };
+        let app = FileInfo::new(
+            "/home/user/repo/app".into(),
+            "app".into(),
+            SimpleFileKind::Directory,
+        );
+        // From above the work tree, and from inside it
+        for root in [setup_fs().current_directory()?, app] {
+            let notifier = VecWalkNotifier::default();
+            let walker = Walker::new(
+                setup_fs(),
+                vec![Matcher::with_remove_strategy(
+                    "Gradle".into(),
+                    Pattern::new("build.gradle")?,
+                    Pattern::new("build")?,
+                )?],
+                &notifier,
+                WalkOptions {
+                    check_git: true,
+                    ..WalkOptions::default()
+                },
+            );
+            walker.walk_from_path(&root);
+
+            let mut found: Vec<_> = notifier
+                .to_remove
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.target().path.clone(), c.tracked_files))
+                .collect();
+            found.sort();
+            let mut expected = vec![(PathBuf::from("/home/user/repo/app/build"), 1)];
+            if root.path == Path::new("/home/user") {
+                expected.push((PathBuf::from("/home/user/repo/lib/build"), 0));
+            }
+            assert_eq!(found, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn removes_what_git_ignores() -> eyre::Result<()> {
+        let index = encode_index(2, &["Cargo.toml", "web/coverage/report.html"]);
+        let setup_fs = || {
+            MockFS::new(MockFSNode::dir(
+                "/",
+                vec![MockFSNode::dir(
+                    "home",
+                    vec![MockFSNode::dir(
+                        "user",
+                        vec![
+                            MockFSNode::dir(
+                                "repo",
+                                vec![
+                                    MockFSNode::dir(
+                                        ".git",
+                                        vec![
+                                            MockFSNode::file_with_bytes("index", index.clone()),
+                                            MockFSNode::dir(
+                                                "info",
+                                                vec![MockFSNode::file_with_content(
+                                                    "exclude", "*.tmp\n",
+                                                )],
+                                            ),
+                                        ],
+                                    ),
+                                    MockFSNode::file_with_content(
+                                        ".gitignore",
+                                        "target/\ndist/\n*.log\n*.pem\n*.cache\n!keep.cache\n.env\n",
+                                    ),
+                                    MockFSNode::file("Cargo.toml"),
+                                    MockFSNode::dir("target", vec![MockFSNode::file("app")]),
+                                    // Ignored, but excluded by default
+                                    MockFSNode::dir(".env", vec![MockFSNode::file("prod")]),
+                                    // Ignored files are never proposed
+                                    MockFSNode::file("debug.log"),
+                                    MockFSNode::file("deploy.pem"),
+                                    MockFSNode::dir(
+                                        "build.cache",
+                                        vec![MockFSNode::file("entry")],
+                                    ),
+                                    MockFSNode::dir("keep.cache", vec![MockFSNode::file("entry")]),
+                                    MockFSNode::dir("scratch.tmp", vec![MockFSNode::file("entry")]),
+                                    MockFSNode::dir(
+                                        "web",
+                                        vec![
+                                            MockFSNode::file_with_content(
+                                                ".gitignore",
+                                                "!dist/\ncoverage/\n",
+                                            ),
+                                            MockFSNode::dir(
+                                                "dist",
+                                                vec![MockFSNode::file("app.js")],
+                                            ),
+                                            MockFSNode::dir(
+                                                "coverage",
+                                                vec![MockFSNode::file("report.html")],
+                                            ),
+                                            MockFSNode::file("trace.log"),
+                                            MockFSNode::dir(
+                                                "tmp.cache",
+                                                vec![MockFSNode::file("entry")],
+                                            ),
+                                        ],
+                                    ),
+                                ],
+                            ),
+                            // Not a git work tree
+                            MockFSNode::dir(
+                                "other",
+                                vec![
+                                    MockFSNode::file_with_content(".gitignore", "dist/\n"),
+                                    MockFSNode::dir("dist", vec![MockFSNode::file("app.js")]),
+                                ],
+                            ),
+                        ],
+                    )],
+                )],
+            ))
+        };
+        let web = FileInfo::new(
+            "/home/user/repo/web".into(),
+            "web".into(),
+            SimpleFileKind::Directory,
+        );
+        // From above the work tree, and from inside it
+        for root in [setup_fs().current_directory()?, web] {
+            let notifier = VecWalkNotifier::default();
+            let walker = Walker::new(
+                setup_fs(),
+                vec![
+                    Matcher::with_remove_strategy(
+                        "Cargo".into(),
+                        Pattern::new("Cargo.toml")?,
+                        Pattern::new("target")?,
+                    )?,
+                    Matcher::with_ignored_strategy("Ignored".into(), Pattern::new("*")?, vec![]),
+                ],
+                &notifier,
+                WalkOptions::default(),
+            );
+            walker.walk_from_path(&root);
+
+            let mut found: Vec<_> = notifier
+                .to_remove
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|c| {
+                    let path = c.target().path.clone();
+                    (path, c.matcher_name.to_string(), c.tracked_files)
+                })
+                .collect();
+            found.sort();
+            let candidate = |path: &str, matcher: &str, tracked| {
+                (PathBuf::from(path), matcher.to_string(), tracked)
+            };
+            let mut expected = vec![
+                candidate("/home/user/repo/web/coverage", "Ignored", 1),
+                candidate("/home/user/repo/web/tmp.cache", "Ignored", 0),
+            ];
+            if root.path == Path::new("/home/user") {
+                expected.splice(
+                    0..0,
+                    [
+                        candidate("/home/user/repo/build.cache", "Ignored", 0),
+                        candidate("/home/user/repo/scratch.tmp", "Ignored", 0),
+                        candidate("/home/user/repo/target", "Cargo", 0),
+                    ],
+                );
+            }
+            assert_eq!(found, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn removes_nested_paths_once() -> eyre::Result<()> {
+        let setup_fs = || {
+            MockFS::new(MockFSNode::dir(
+                "/",
+                vec![MockFSNode::dir(
+                    "home",
+                    vec![MockFSNode::dir(
+                        "user",
+                        vec![MockFSNode::dir(
+                            "mobile",
+                            vec![
+                                MockFSNode::file("pubspec.yaml"),
+                                MockFSNode::dir("build", vec![MockFSNode::file("app.dill")]),
+                                MockFSNode::dir(
+                                    "android",
+                                    vec![MockFSNode::dir(
+                                        "app",
+                                        vec![
+                                            MockFSNode::file("build.gradle"),
+                                            MockFSNode::dir(
+                                                "build",
+                                                vec![MockFSNode::dir(
+                                                    "build",
+                                                    vec![MockFSNode::file("app.apk")],
+                                                )],
+                                            ),
+                                        ],
+                                    )],
+                                ),
+                                MockFSNode::dir(
+                                    "ios",
+                                    vec![
+                                        // Not a directory
+                                        MockFSNode::file("Pods"),
+                                        MockFSNode::dir(
+                                            "Runner",
+                                            vec![MockFSNode::dir(
+                                                "Pods",
+                                                vec![MockFSNode::file("Podfile.lock")],
+                                            )],
+                                        ),
+                                    ],
+                                ),
+                            ],
+                        )],
+                    )],
+                )],
+            ))
+        };
+        for threads in [1, 4] {
+            let fs = setup_fs();
+            let current_dir = fs.current_directory()?;
+            let notifier = VecWalkNotifier::default();
+            let walker = Walker::new(
+                fs,
+                vec![
+                    Matcher::with_remove_strategy(
+                        "Android".into(),
+                        Pattern::new("pubspec.yaml")?,
+                        Pattern::new("android/app/build")?,
+                    )?,
+                    Matcher::with_remove_strategy(
+                        "Pods".into(),
+                        Pattern::new("pubspec.yaml")?,
+                        Pattern::new("ios/**/Pods/")?,
+                    )?,
+                    // Overlaps the Android target, and holds a nested match
+                    Matcher::with_remove_strategy(
+                        "Builds".into(),
+                        Pattern::new("pubspec.yaml")?,
+                        Pattern::new("**/build")?,
+                    )?,
+                    Matcher::with_remove_strategy(
+                        "Gradle".into(),
+                        Pattern::new("build.gradle")?,
+                        Pattern::new("build")?,
+                    )?,
+                ],
+                &notifier,
+                WalkOptions {
+                    threads,
+                    ..WalkOptions::default()
+                },
+            );
+            walker.walk_from_path(&current_dir);
+
+            let mut found: Vec<_> = notifier
+                .to_remove
+                .into_inner()
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.target().path.clone(), c.matcher_name.to_string()))
+                .collect();
+            found.sort();
+            assert_eq!(
+                found,
+                [
+                    (
+                        PathBuf::from("/home/user/mobile/android/app/build"),
+                        "Android".to_string()
+                    ),
+                    (
+                        PathBuf::from("/home/user/mobile/build"),
+                        "Builds".to_string()
+                    ),
+                    (
+                        PathBuf::from("/home/user/mobile/ios/Runner/Pods"),
+                        "Pods".to_string()
+                    ),
+                ]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested_paths_are_looked_for_where_the_walk_goes() -> eyre::Result<()> {
+        let build = || MockFSNode::dir("build", vec![MockFSNode::file("out.o")]);
+        let fs = MockFS::new(MockFSNode::dir(
+            "/",
+            vec![MockFSNode::dir(
+                "home",
+                vec![MockFSNode::dir(
+                    "user",
+                    vec![MockFSNode::dir(
+                        "mono",
+                        vec![
+                            MockFSNode::file("WORKSPACE"),
+                            MockFSNode::dir("src", vec![build()]),
+                            MockFSNode::dir(".cache", vec![build()]),
+                            MockFSNode::dir(
+                                ".gradle",
+                                vec![MockFSNode::dir("caches", vec![MockFSNode::file("bin")])],
+                            ),
+                            MockFSNode::dir("vendor", vec![build()]),
+                            MockFSNode::dir(
+                                "lib",
+                                vec![
+                                    MockFSNode::file_with_content(
+                                        ".ocy.toml",
+                                        r#"protect = ["build"]"#,
+                                    ),
+                                    build(),
+                                ],
+                            ),
+                            MockFSNode::dir("usb", vec![build()]).mounted(1, FileSystemKind::Local),
+                        ],
+                    )],
+                )],
+            )],
+        ));
+        let current_dir = fs.current_directory()?;
+        let notifier = VecWalkNotifier::default();
+        let walker = Walker::new(
+            fs,
+            vec![
+                Matcher::with_remove_strategy(
+                    "Builds".into(),
+                    Pattern::new("WORKSPACE")?,
+                    Pattern::new("**/build")?,
+                )?,
+                Matcher::with_remove_strategy(
+                    "Caches".into(),
+                    Pattern::new("WORKSPACE")?,
+                    Pattern::new(".gradle/caches")?,
+                )?,
+            ],
+            &notifier,
+            WalkOptions {
+                ignores: [PathBuf::from("/home/user/mono/vendor")].into(),
+                one_file_system: true,
+                ..WalkOptions::default()
+            },
+        );
+        walker.walk_from_path(&current_dir);
+
+        let found: Vec<_> = notifier
+            .to_remove
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|c| c.target().path.clone())
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                PathBuf::from("/home/user/mono/src/build"),
+                PathBuf::from("/home/user/mono/.gradle/caches"),
+            ]
+        );
+        assert_eq!(
+            notifier.skipped.into_inner().unwrap(),
+            vec![(
+                PathBuf::from("/home/user/mono/usb"),
+                SkipReason::OtherFileSystem
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn older_than_skips_recently_modified_projects() -> eyre::Result<()> {
+        let now = SystemTime::now();
+        let days_ago = |days: u64| now - Duration::from_secs(days * 24 * 60 * 60);
+        let project = |name: &str, source_modified| {
+            MockFSNode::dir(
+                name,
+                vec![
+                    MockFSNode::file("Cargo.toml").modified(days_ago(30)),
+                    MockFSNode::dir(
+                        "src",
+                        vec![MockFSNode::file("main.rs").modified(source_modified)],
+                    ),
+                    // Build outputs do not make a project recent
+                    MockFSNode::dir(
+                        "target",
+                        vec![MockFSNode::file("app").modified(days_ago(0))],
+                    ),
+                ],
+            )
+        };
+        let fs = MockFS::new(MockFSNode::dir(
+            "/",
+            vec![MockFSNode::dir(
+                "home",
+                vec![MockFSNode::dir(
+                    "user",
+                    vec![
+                        project("old", days_ago(10)),
+                        project("recent", days_ago(2)),
+                        // Edited deep down, in place, which leaves the
+                        // directories holding it older
+                        MockFSNode::dir(
+                            "deep",
+                            vec![
+                                MockFSNode::file("Cargo.toml").modified(days_ago(30)),
+                                MockFSNode::file("target"),
+                                MockFSNode::dir(
+                                    "a",
+                                    vec![MockFSNode::dir(
+                                        "b",
+                                        vec![MockFSNode::dir(
+                                            "c",
+                                            vec![MockFSNode::dir(
+                                                "d",
+                                                vec![MockFSNode::file("main.rs")
+                                                    .modified(days_ago(2))],
+                                            )
+                                            .modified(days_ago(10))],
+                                        )],
+                                    )],
+                                ),
+                            ],
+                        ),
+                    ],
+                )],
+            )],
+        ));
+        let current_dir = fs.current_directory()?;
+        let notifier = VecWalkNotifier::default();
+        let walker = Walker::new(
+            fs,
+            vec![Matcher::with_remove_strategy(
+                "Cargo".into(),
+                Pattern::new("Cargo.toml")?,
+                Pattern::new("target")?,
+            )?],
+            &notifier,
+            WalkOptions {
+                older_than: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+                ..WalkOptions::default()
+            },
+        );
+        walker.walk_from_path(&current_dir);
+
+        let found: Vec<_> = notifier
+            .to_remove
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|c| match c.action {
+                RemovalAction::Delete { file_info, .. } => file_info.path,
+                RemovalAction::RunCommand { .. } => panic!("should be delete"),
+            })
+            .collect();
+        assert_eq!(found, vec![PathBuf::from("/home/user/old/target")]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,136 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Runs `process` on `roots` and on the tasks it spawns, on `threads`
+/// threads. Each thread works depth first on its own queue and steals the
+/// oldest tasks of the others when it runs out.
+pub(crate) fn run<T, F>(threads: usize, roots: Vec<T>, process: F)
+where
+    T: Send,
+    F: Fn(T, &mut Vec<T>) + Sync,
+{
+    let threads = threads.max(1);
+    let shared = Shared {
+        queues: (0..threads).map(|_| Mutex::default()).collect(),
+        pending: AtomicUsize::new(roots.len()),
+        idle: Mutex::default(),
+        wake: Condvar::new(),
+    };
+    for (i, root) in roots.into_iter().enumerate() {
+        shared.queues[i % threads].lock().unwrap().push_back(root);
+    }
+
+    thread::scope(|scope| {
+        for id in 0..threads {
+            let shared = &shared;
+            let process = &process;
+            scope.spawn(move || shared.work(id, process));
+        }
+    });
+}
+
+struct Shared<T> {
+    queues: Vec<Mutex<VecDeque<T>>>,
+    /// Tasks queued or being processed.
+    pending: AtomicUsize,
+    idle: Mutex<()>,
+    wake: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn work(&self, id: usize, process: &impl Fn(T, &mut Vec<T>)) {
+        let mut spawned = Vec::new();
+        loop {
+            if let Some(task) = self.pop(id) {
+                let _done = TaskDone(self);
+                process(task, &mut spawned);
+                if !spawned.is_empty() {
+                    // Counted before this task is done, so that pending
+                    // cannot drop to zero while work remains.
+                    self.pending.fetch_add(spawned.len(), Ordering::SeqCst);
+                    self.queues[id].lock().unwrap().extend(spawned.drain(..));
+                    self.wake.notify_all();
+                }
+            } else if self.pending.load(Ordering::SeqCst) == 0 {
+                return;
+            } else {
+                // Bounded wait, a wake up may be missed between the checks
+                // above and this wait.
+                let idle = self.idle.lock().unwrap();
+                let _ = self
+                    .wake
+                    .wait_timeout(idle, Duration::from_millis(1))
+                    .unwrap();
+            }
+        }
+    }
+
+    fn pop(&self, id: usize) -> Option<T> {
+        if let Some(task) = self.queues[id].lock().unwrap().pop_back() {
+            return Some(task);
+        }
+        let n = self.queues.len();
+        (1..n).find_map(|offset| self.queues[(id + offset) % n].lock().unwrap().pop_front())
+    }
+}
+
+/// Counts a task as done when dropped, after the tasks it spawned are queued,
+/// or when it panics so that the other threads do not wait for it forever.
+struct TaskDone<'a, T>(&'a Shared<T>);
+
+impl<T> Drop for TaskDone<'_, T> {
+    fn drop(&mut self) {
+        if self.0.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.wake.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, sync::Mutex};
+
+    use super::run;
+
+    #[test]
+    fn processes_every_spawned_task_once() {
+        // Each task n > 0 spawns two tasks n - 1: 2^(depth + 1) - 1 tasks.
+        let depth = 12;
+        let seen = Mutex::new(Vec::new());
+
+        run(4, vec![depth], |n: u32, spawned| {
+            seen.lock().unwrap().push(n);
+            if n > 0 {
+                spawned.extend([n - 1, n - 1]);
+            }
+        });
+
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), (1 << (depth + 1)) - 1);
+        assert_eq!(seen.iter().filter(|&&n| n == 0).count(), 1 << depth);
+    }
+
+    #[test]
+    fn panics_are_raised_once_the_other_tasks_are_done() {
+        let seen = Mutex::new(0);
+
+        let result = panic::catch_unwind(|| {
+            run(4, (0..64).collect(), |n: u32, _| {
+                if n == 10 {
+                    panic!("task {} failed", n);
+                }
+                *seen.lock().unwrap() += 1;
+            })
+        });
+
+        assert!(result.is_err());
+        assert_eq!(seen.into_inner().unwrap(), 63);
+    }
+}
@@ -0,0 +1,128 @@
+use colored::Colorize;
+use eyre::{bail, Context, Result};
+use ocy_core::{
+    cleaner::Cleaner,
+    command::RealCommandExecutor,
+    disk::DiskUsage,
+    filesystem::{FileSystem, RealFileSystem},
+    selection::reclaim,
+};
+
+use crate::{
+    budget::print_budget,
+    events::EventWriter,
+    keep_top,
+    matchers::load_matchers,
+    notifiers::{LoggingCleanerNotifier, VecWalkNotifier},
+    options::{AutoOptions, OcyOptions},
+    perform_dry_run, perform_walk, print_skipped_tracked, skip_tracked,
+    utils::format_file_size,
+};
+
+/// Share of the disk freed above the low-water mark when `--until-free` is
+/// not given.
+const DEFAULT_HEADROOM_PERCENT: u64 = 5;
+
+/// Walks and cleans only when free space is below the low-water mark, and
+/// stops cleaning as soon as it is back above the high-water mark. Never asks
+/// for confirmation, so that it can run unattended.
+pub fn run(mut options: OcyOptions, auto_options: &AutoOptions) -> Result<()> {
+    let Some(when_free_below) = auto_options.when_free_below else {
+        bail!("`auto` needs --when-free-below");
+    };
+    if options.trash || options.quarantine {
+        bail!("`auto` deletes files to free space, --trash and --quarantine would not");
+    }
+    // Free space is only measured where the walk starts, cleaning other file
+    // systems would never bring it back above the high-water mark.
+    options.one_file_system = true;
+    let options = &options;
+
+    let current_directory = RealFileSystem::default()
+        .current_directory()
+        .wrap_err("Cannot scan current directory")?;
+    let root = current_directory.path.clone();
+    let usage = DiskUsage::of(&root)?;
+    let low = when_free_below.bytes(usage.total);
+    let high = auto_options
+        .until_free
+        .map_or(low + usage.total / 100 * DEFAULT_HEADROOM_PERCENT, |t| {
+            t.bytes(usage.total)
+        })
+        .max(low);
+
+    eprintln!(
+        "Free space: {} of {}",
+        format_file_size(usage.available),
+        format_file_size(usage.total)
+    );
+    if usage.available >= low {
+        eprintln!("Above {}, nothing to do", format_file_size(low));
+        return Ok(());
+    }
+
+    let matchers = load_matchers(options.config.as_deref(), options.gitignore)?;
+    let events = options
+        .events
+        .as_deref()
+        .map(EventWriter::create)
+        .transpose()?;
+    let notifier = VecWalkNotifier::new(&root);
+    match &events {
+        Some(events) => perform_walk(
+            options,
+            &current_directory,
+            matchers,
+            events.wrap(&notifier),
+        ),
+        None => perform_walk(options, &current_directory, matchers, &notifier),
+    }
+    let (files, tracked) = skip_tracked(options, notifier.to_remove.into_inner().unwrap());
+    print_skipped_tracked(&tracked);
+    let files = keep_top(options, files);
+    if files.is_empty() {
+        eprintln!("No projects found");
+        std::process::exit(1);
+    }
+
+    let budget = reclaim(files, high - usage.available, options.strategy);
+    print_budget(&root, &budget);
+    if options.dry_run {
+        // Moving files elsewhere would not free space, auto always deletes.
+        perform_dry_run(&current_directory, budget.selected, None, events.as_ref());
+        return Ok(());
+    }
+
+    // Estimates may be off, the disk itself tells when to stop.
+    let count = budget.selected.len() + budget.untouched.len();
+    let files = budget
+        .selected
+        .into_iter()
+        .chain(budget.untouched)
+        .take_while(|_| DiskUsage::of(&root).is_ok_and(|usage| usage.available < high));
+    let ce = RealCommandExecutor::default();
+    let (jobs, removal_jobs) = options.jobs(count);
+    let fs = options.file_system().with_jobs(removal_jobs);
+    let notifier = LoggingCleanerNotifier::new(&root, count);
+    match &events {
+        Some(events) => Cleaner::new(files, fs, ce, events.wrap(&notifier))
+            .with_jobs(jobs)
+            .clean(),
+        None => Cleaner::new(files, fs, ce, &notifier)
+            .with_jobs(jobs)
+            .clean(),
+    }
+
+    let available = DiskUsage::of(&root)?.available;
+    let line = format!(
+        "Free space is now {}, aiming for {}",
+        format_file_size(available),
+        format_file_size(high)
+    );
+    if available >= high {
+        eprintln!("{}", line.green());
+    } else {
+        eprintln!("{}", line.yellow());
+    }
+    Ok(())
+}
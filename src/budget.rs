@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use colored::Colorize;
+use ocy_core::selection::Budget;
+
+use crate::{
+    notifiers::format_candidate,
+    total_size,
+    utils::{format_file_size, format_file_size_and_more, format_opt_file_size},
+};
+
+/// Tells how much of the `--reclaim` target is met and what is left alone.
+pub fn print_budget(base_path: &Path, budget: &Budget) {
+    for line in budget_lines(base_path, budget) {
+        eprintln!("{}", line);
+    }
+}
+
+fn budget_lines(base_path: &Path, budget: &Budget) -> Vec<String> {
+    let target = format_file_size(budget.target);
+    let reclaimed = format_file_size(budget.reclaimed());
+    let mut lines = vec![if budget.is_met() {
+        format!(
+            "Budget: {} candidate(s) reclaim {} of the {} target",
+            budget.selected.len(),
+            reclaimed.cyan(),
+            target
+        )
+    } else {
+        format!(
+            "Budget: only {} of the {} target can be reclaimed",
+            reclaimed, target
+        )
+        .yellow()
+        .to_string()
+    }];
+
+    if budget.untouched.is_empty() {
+        return lines;
+    }
+    let (size, has_more) = total_size(&budget.untouched);
+    lines.push(format!(
+        "Left untouched: {} candidate(s), {}",
+        budget.untouched.len(),
+        format_file_size_and_more(size, has_more)
+    ));
+    lines.extend(budget.untouched.iter().map(|candidate| {
+        format!(
+            "  {:>9} {}",
+            format_opt_file_size(candidate.file_size()),
+            format_candidate(base_path, candidate)
+        )
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ocy_core::{
+        models::{FileInfo, RemovalCandidate, SimpleFileKind},
+        selection::{reclaim, Strategy},
+    };
+
+    use super::budget_lines;
+
+    fn candidate(name: &str, size: Option<u64>) -> RemovalCandidate {
+        let file = FileInfo {
+            path: Path::new("/p").join(name),
+            name: name.into(),
+            kind: SimpleFileKind::Directory,
+            modified: None,
+            device: None,
+        };
+        RemovalCandidate::new("Cargo".into(), file, size)
+    }
+
+    #[test]
+    fn reports_the_budget_and_what_is_left() {
+        colored::control::set_override(false);
+        let candidates = || {
+            vec![
+                candidate("small", Some(1024)),
+                candidate("large", Some(4096)),
+                candidate("unknown", None),
+            ]
+        };
+
+        let met = reclaim(candidates(), 2048, Strategy::Largest);
+        assert_eq!(
+            budget_lines(Path::new("/p"), &met),
+            vec![
+                "Budget: 1 candidate(s) reclaim 4.00 KiB of the 2.00 KiB target",
+                "Left untouched: 2 candidate(s), 1.00 KiB+",
+                "   1.00 KiB small",
+                "          - unknown",
+            ]
+        );
+
+        let short = reclaim(candidates(), 1 << 20, Strategy::Largest);
+        assert_eq!(
+            budget_lines(Path::new("/p"), &short),
+            vec!["Budget: only 5.00 KiB of the 1.00 MiB target can be reclaimed"]
+        );
+    }
+}
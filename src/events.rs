@@ -0,0 +1,297 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use eyre::{Context, Report, Result};
+use ocy_core::{
+    cleaner::{CleanSummary, CleanerNotifier},
+    models::{FileInfo, RemovalCandidate},
+    walker::{SkipReason, WalkNotifier},
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    EnteredDirectory {
+        path: &'a Path,
+    },
+    Candidate {
+        candidate: &'a RemovalCandidate,
+    },
+    CandidateSize {
+        candidate: &'a RemovalCandidate,
+    },
+    FailToScan {
+        path: &'a Path,
+        error: String,
+    },
+    Skipped {
+        path: &'a Path,
+        reason: SkipReason,
+    },
+    RemovalStarted {
+        candidate: &'a RemovalCandidate,
+    },
+    RemovalSuccess {
+        candidate: &'a RemovalCandidate,
+        freed: Option<u64>,
+    },
+    RemovalFailed {
+        candidate: &'a RemovalCandidate,
+        error: String,
+    },
+    Finish {
+        phase: Phase,
+        #[serde(flatten)]
+        summary: Option<&'a CleanSummary>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Phase {
+    Walk,
+    Clean,
+}
+
+/// Writes walk and clean events as newline-delimited JSON.
+pub struct EventWriter {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EventWriter {
+    /// Writes to `path`, or to stdout when it is `-`.
+    pub fn create(path: &Path) -> Result<Self> {
+        let out: Box<dyn Write + Send> = if path == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            let file = File::create(path)
+                .wrap_err_with(|| format!("Cannot create event file {}", path.display()))?;
+            Box::new(BufWriter::new(file))
+        };
+        Ok(Self {
+            out: Mutex::new(out),
+        })
+    }
+
+    /// Notifier writing events before forwarding them to `inner`.
+    pub fn wrap<N>(&self, inner: N) -> EventNotifier<'_, N> {
+        EventNotifier {
+            events: self,
+            inner,
+        }
+    }
+
+    fn write(&self, event: Event) {
+        // Event streams are best effort, a closed pipe must not abort a clean.
+        let mut out = self.out.lock().unwrap();
+        let _ = serde_json::to_writer(&mut *out, &event);
+        let _ = out.write_all(b"\n");
+        let _ = out.flush();
+    }
+}
+
+pub struct EventNotifier<'a, N> {
+    events: &'a EventWriter,
+    inner: N,
+}
+
+impl<'a, N: WalkNotifier> WalkNotifier for EventNotifier<'a, N> {
+    fn notify_entered_directory(&self, dir: &FileInfo) {
+        self.events
+            .write(Event::EnteredDirectory { path: &dir.path });
+        self.inner.notify_entered_directory(dir);
+    }
+
+    fn notify_candidate_for_removal(&self, candidate: RemovalCandidate) {
+        self.events.write(Event::Candidate {
+            candidate: &candidate,
+        });
+        self.inner.notify_candidate_for_removal(candidate);
+    }
+
+    fn notify_candidate_size_computed(&self, candidate: RemovalCandidate) {
+        self.events.write(Event::CandidateSize {
+            candidate: &candidate,
+        });
+        self.inner.notify_candidate_size_computed(candidate);
+    }
+
+    fn notify_fail_to_scan(&self, e: &FileInfo, report: Report) {
+        self.events.write(Event::FailToScan {
+            path: &e.path,
+            error: format!("{:#}", report),
+        });
+        self.inner.notify_fail_to_scan(e, report);
+    }
+
+    fn notify_skipped(&self, file: &FileInfo, reason: SkipReason) {
+        self.events.write(Event::Skipped {
+            path: &file.path,
+            reason,
+        });
+        self.inner.notify_skipped(file, reason);
+    }
+
+    fn notify_walk_finish(&self) {
+        self.events.write(Event::Finish {
+            phase: Phase::Walk,
+            summary: None,
+        });
+        self.inner.notify_walk_finish();
+    }
+}
+
+impl<'a, N: CleanerNotifier> CleanerNotifier for EventNotifier<'a, N> {
+    fn notify_removal_started(&self, candidate: &RemovalCandidate) {
+        self.events.write(Event::RemovalStarted { candidate });
+        self.inner.notify_removal_started(candidate);
+    }
+
+    fn notify_removal_success(&self, candidate: RemovalCandidate, freed: Option<u64>) {
+        self.events.write(Event::RemovalSuccess {
+            candidate: &candidate,
+            freed,
+        });
+        self.inner.notify_removal_success(candidate, freed);
+    }
+
+    fn notify_removal_failed(&self, candidate: RemovalCandidate, report: Report) {
+        self.events.write(Event::RemovalFailed {
+            candidate: &candidate,
+            error: format!("{:#}", report),
+        });
+        self.inner.notify_removal_failed(candidate, report);
+    }
+
+    fn notify_removal_finish(&self, summary: &CleanSummary) {
+        self.events.write(Event::Finish {
+            phase: Phase::Clean,
+            summary: Some(summary),
+        });
+        self.inner.notify_removal_finish(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ocy_core::{
+        cleaner::CleanSummary,
+        models::{FileInfo, RemovalCandidate, SimpleFileKind},
+        walker::SkipReason,
+    };
+    use serde_json::{json, Value};
+
+    use super::{Event, Phase};
+
+    fn to_json(event: Event) -> Value {
+        serde_json::to_value(event).unwrap()
+    }
+
+    #[test]
+    fn events_are_tagged_with_their_kind() {
+        let file = FileInfo {
+            path: "/p/target".into(),
+            name: "target".into(),
+            kind: SimpleFileKind::Directory,
+            modified: None,
+            device: None,
+        };
+        let candidate = &RemovalCandidate::new("Cargo".into(), file, Some(42));
+        let candidate_json = json!({
+            "matcher_name": "Cargo",
+            "kind": "delete",
+            "file_info": { "path": "/p/target", "name": "target", "kind": "directory" },
+            "file_size": 42,
+            "tracked_files": 0,
+        });
+        let path = Path::new("/p");
+
+        for (event, expected) in [
+            (
+                Event::EnteredDirectory { path },
+                json!({ "event": "entered_directory", "path": "/p" }),
+            ),
+            (
+                Event::Candidate { candidate },
+                json!({ "event": "candidate", "candidate": candidate_json }),
+            ),
+            (
+                Event::CandidateSize { candidate },
+                json!({ "event": "candidate_size", "candidate": candidate_json }),
+            ),
+            (
+                Event::FailToScan {
+                    path,
+                    error: "denied".into(),
+                },
+                json!({ "event": "fail_to_scan", "path": "/p", "error": "denied" }),
+            ),
+            (
+                Event::Skipped {
+                    path,
+                    reason: SkipReason::NetworkFileSystem,
+                },
+                json!({ "event": "skipped", "path": "/p", "reason": "network_file_system" }),
+            ),
+            (
+                Event::RemovalStarted { candidate },
+                json!({ "event": "removal_started", "candidate": candidate_json }),
+            ),
+            (
+                Event::RemovalSuccess {
+                    candidate,
+                    freed: None,
+                },
+                json!({ "event": "removal_success", "candidate": candidate_json, "freed": null }),
+            ),
+            (
+                Event::RemovalFailed {
+                    candidate,
+                    error: "busy".into(),
+                },
+                json!({ "event": "removal_failed", "candidate": candidate_json, "error": "busy" }),
+            ),
+        ] {
+            assert_eq!(to_json(event), expected);
+        }
+    }
+
+    #[test]
+    fn clean_finish_flattens_the_summary() {
+        assert_eq!(
+            to_json(Event::Finish {
+                phase: Phase::Walk,
+                summary: None,
+            }),
+            json!({ "event": "finish", "phase": "walk" })
+        );
+
+        let summary = CleanSummary {
+            freed: 42,
+            has_unknown: true,
+            moved: 0,
+            disk_freed: Some(40),
+        };
+        assert_eq!(
+            to_json(Event::Finish {
+                phase: Phase::Clean,
+                summary: Some(&summary),
+            }),
+            json!({
+                "event": "finish",
+                "phase": "clean",
+                "freed": 42,
+                "has_unknown": true,
+                "moved": 0,
+                "disk_freed": 40,
+            })
+        );
+    }
+}
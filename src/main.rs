@@ -1,78 +1,356 @@
+#[cfg(unix)]
+mod auto;
+mod budget;
+mod events;
 mod matchers;
 mod notifiers;
 mod options;
+mod quarantine;
+mod select;
+mod tui;
 mod utils;
 
+use budget::print_budget;
 use colored::Colorize;
-use eyre::{Context, Result};
+use events::EventWriter;
+use eyre::{bail, Context, Result};
 use gumdrop::Options;
-use matchers::standard_matchers;
+use matchers::load_matchers;
 use ocy_core::command::RealCommandExecutor;
-use std::{collections::HashSet, path::PathBuf, process::exit};
+use ocy_core::dry_run::{DryRun, PlannedAction};
+use serde::Serialize;
+use std::process::exit;
 
-use ocy_core::filesystem::{FileSystem, RealFileSystem};
+use ocy_core::filesystem::{FileSystem, FileSystemClean, RealFileSystem};
+use ocy_core::matcher::Matcher;
 use ocy_core::models::FileInfo;
-use ocy_core::walker::Walker;
+use ocy_core::quarantine::Quarantine;
+use ocy_core::selection::{largest, reclaim};
+#[cfg(unix)]
+use ocy_core::trash::TrashFileSystemClean;
+use ocy_core::walker::{WalkNotifier, Walker};
 use ocy_core::{cleaner::Cleaner, models::RemovalCandidate};
 
-use notifiers::{LoggingCleanerNotifier, VecWalkNotifier};
-use options::OcyOptions;
-use utils::{format_file_size_and_more, prompt};
+use notifiers::{LoggingCleanerNotifier, PlanCleanerNotifier, VecWalkNotifier};
+use options::{AutoOptions, Command, OcyOptions, OutputFormat};
+use select::select_candidates;
+use utils::{configure_colors, confirm, format_file_size_and_more};
 
 fn main() -> Result<()> {
-    let options = OcyOptions::parse_args_default_or_exit();
+    let mut options = OcyOptions::parse_args_default_or_exit();
+    configure_colors();
 
-    print_banner();
+    if options.trash && options.quarantine {
+        bail!("--trash and --quarantine cannot be used together");
+    }
 
     if options.version {
+        print_banner();
         exit(0);
     }
 
-    let ignores = options.get_ignores_set();
+    if options.format == OutputFormat::Json && options.command.is_some() {
+        bail!("--format json only reports the candidates found, not subcommands");
+    }
+
+    if let Some(Command::Tui(_)) = options.command {
+        return tui::run(&options);
+    }
+
+    match options.command.take() {
+        Some(Command::Restore(restore_options)) => return quarantine::restore(restore_options),
+        Some(Command::Purge(purge_options)) => {
+            return quarantine::purge(purge_options, options.yes)
+        }
+        Some(Command::Auto(auto_options)) => return run_auto(options, &auto_options),
+        Some(Command::Tui(_)) | None => {}
+    }
+
+    if options.format == OutputFormat::Json {
+        return print_json_report(&options);
+    }
 
-    let current_directory = RealFileSystem
+    if !options.events_on_stdout() {
+        print_banner();
+    }
+
+    let matchers = load_matchers(options.config.as_deref(), options.gitignore)?;
+    let events = options
+        .events
+        .as_deref()
+        .map(EventWriter::create)
+        .transpose()?;
+
+    let current_directory = RealFileSystem::default()
         .current_directory()
         .wrap_err("Cannot scan current directory")?;
 
-    let files = perform_walk(&current_directory, ignores, options.walk_all);
+    let notifier = VecWalkNotifier::new(&current_directory.path);
+    match &events {
+        Some(events) => perform_walk(
+            &options,
+            &current_directory,
+            matchers,
+            events.wrap(&notifier),
+        ),
+        None => perform_walk(&options, &current_directory, matchers, &notifier),
+    }
+    let (files, tracked) = skip_tracked(&options, notifier.to_remove.into_inner().unwrap());
+    print_skipped_tracked(&tracked);
+    let mut files = keep_top(&options, files);
     if files.is_empty() {
-        println!("No projects found");
+        eprintln!("No projects found");
         exit(1);
     }
-    println!();
+
+    if let Some(target) = options.reclaim {
+        let budget = reclaim(files, target, options.strategy);
+        print_budget(&current_directory.path, &budget);
+        files = budget.selected;
+        if files.is_empty() {
+            eprintln!("Nothing to clean");
+            return Ok(());
+        }
+    }
+
+    if options.select {
+        files = select_candidates(&current_directory.path, files)?;
+        if files.is_empty() {
+            eprintln!("Nothing selected");
+            return Ok(());
+        }
+    }
+
+    if options.dry_run {
+        perform_dry_run(
+            &current_directory,
+            files,
+            moved_to(&options),
+            events.as_ref(),
+        );
+        return Ok(());
+    }
 
     let (total_size, has_more) = total_size(&files);
 
-    if prompt(&format!(
-        "Reclaim {} (y/N) ? ",
-        format_file_size_and_more(total_size, has_more).cyan(),
-    )) {
-        perform_clean(&current_directory, files);
+    if confirm(
+        &format!(
+            "Reclaim {} (y/N) ? ",
+            format_file_size_and_more(total_size, has_more).cyan(),
+        ),
+        options.yes,
+    ) {
+        let events = events.as_ref();
+        if options.trash {
+            perform_clean(
+                &options,
+                &current_directory,
+                files,
+                trash_file_system(options.file_system())?,
+                events,
+            );
+        } else if options.quarantine {
+            let quarantine = Quarantine::new()
+                .wrap_err("Cannot locate the quarantine")?
+                .with_sizes(options.file_system());
+            perform_clean(&options, &current_directory, files, quarantine, events);
+        } else {
+            let (_, removal_jobs) = options.jobs(files.len());
+            perform_clean(
+                &options,
+                &current_directory,
+                files,
+                options.file_system().with_jobs(removal_jobs),
+                events,
+            );
+        }
     }
 
     Ok(())
 }
 
 fn perform_walk(
+    options: &OcyOptions,
     current_directory: &FileInfo,
-    ignores: HashSet<PathBuf>,
-    walk_all: bool,
-) -> Vec<RemovalCandidate> {
-    let fs = RealFileSystem;
-    let matchers = standard_matchers();
-    let notifier = VecWalkNotifier::new(&current_directory.path);
-    let walker = Walker::new(fs, matchers, &notifier, ignores, walk_all);
+    matchers: Vec<Matcher>,
+    notifier: impl WalkNotifier,
+) {
+    let fs = options.file_system();
+    let walker = Walker::new(fs, matchers, notifier, options.walk_options());
 
     walker.walk_from_path(current_directory);
-    notifier.to_remove.into_inner()
 }
 
-fn perform_clean(current_directory: &FileInfo, files: Vec<RemovalCandidate>) {
-    let fs = RealFileSystem;
-    let ce = RealCommandExecutor;
+fn perform_clean(
+    options: &OcyOptions,
+    current_directory: &FileInfo,
+    files: Vec<RemovalCandidate>,
+    fs: impl FileSystemClean,
+    events: Option<&EventWriter>,
+) {
+    let ce = RealCommandExecutor::default();
     let notifier = LoggingCleanerNotifier::new(&current_directory.path, files.len());
-    let cleaner = Cleaner::new(files, fs, ce, &notifier);
-    cleaner.clean();
+    let (jobs, _) = options.jobs(files.len());
+    match events {
+        Some(events) => Cleaner::new(files, fs, ce, events.wrap(&notifier))
+            .with_jobs(jobs)
+            .clean(),
+        None => Cleaner::new(files, fs, ce, &notifier)
+            .with_jobs(jobs)
+            .clean(),
+    }
+}
+
+#[cfg(unix)]
+fn trash_file_system(sizes: RealFileSystem) -> Result<TrashFileSystemClean> {
+    Ok(TrashFileSystemClean::new()
+        .wrap_err("Cannot locate the trash")?
+        .with_sizes(sizes))
+}
+
+#[cfg(not(unix))]
+fn trash_file_system(_sizes: RealFileSystem) -> Result<RealFileSystem> {
+    eyre::bail!("--trash is only supported on Unix")
+}
+
+#[cfg(unix)]
+fn run_auto(options: OcyOptions, auto_options: &AutoOptions) -> Result<()> {
+    auto::run(options, auto_options)
+}
+
+#[cfg(not(unix))]
+fn run_auto(_options: OcyOptions, _auto_options: &AutoOptions) -> Result<()> {
+    eyre::bail!("`auto` is only supported on Unix")
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    candidates: &'a [RemovalCandidate],
+    total_size: u64,
+    has_more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    budget: Option<JsonBudget<'a>>,
+    /// Candidates left out because they hold files tracked by git.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tracked: &'a [RemovalCandidate],
+}
+
+#[derive(Serialize)]
+struct JsonBudget<'a> {
+    target: u64,
+    reclaimed: u64,
+    met: bool,
+    untouched: &'a [RemovalCandidate],
+}
+
+fn print_json_report(options: &OcyOptions) -> Result<()> {
+    let matchers = load_matchers(options.config.as_deref(), options.gitignore)?;
+    let current_directory = RealFileSystem::default()
+        .current_directory()
+        .wrap_err("Cannot scan current directory")?;
+
+    let notifier = VecWalkNotifier::silent(&current_directory.path);
+    perform_walk(options, &current_directory, matchers, &notifier);
+    let (files, tracked) = skip_tracked(options, notifier.to_remove.into_inner().unwrap());
+    let files = keep_top(options, files);
+    let budget = options
+        .reclaim
+        .map(|target| reclaim(files.clone(), target, options.strategy));
+    let candidates = budget.as_ref().map_or(&files[..], |b| &b.selected[..]);
+    let (total_size, has_more) = total_size(candidates);
+
+    let report = JsonReport {
+        candidates,
+        total_size,
+        has_more,
+        budget: budget.as_ref().map(|b| JsonBudget {
+            target: b.target,
+            reclaimed: b.reclaimed(),
+            met: b.is_met(),
+            untouched: &b.untouched,
+        }),
+        tracked: &tracked,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+/// Where `--trash` or `--quarantine` move removed files, if given.
+fn moved_to(options: &OcyOptions) -> Option<&'static str> {
+    if options.trash {
+        Some("trash")
+    } else if options.quarantine {
+        Some("quarantine")
+    } else {
+        None
+    }
+}
+
+/// Prints what cleaning `files` would do, files being moved to `moved_to`
+/// rather than deleted when given.
+fn perform_dry_run(
+    current_directory: &FileInfo,
+    files: Vec<RemovalCandidate>,
+    moved_to: Option<&str>,
+    events: Option<&EventWriter>,
+) {
+    let removal = format!("Would {}", moved_to.unwrap_or("remove"));
+    let moves_files = moved_to.is_some();
+
+    eprintln!();
+    let dry_run = DryRun::default().with_moves_files(moves_files);
+    let notifier = PlanCleanerNotifier::new(&current_directory.path, removal);
+    match events {
+        Some(events) => Cleaner::new(files, &dry_run, &dry_run, events.wrap(&notifier)).clean(),
+        None => Cleaner::new(files, &dry_run, &dry_run, &notifier).clean(),
+    }
+
+    let actions = dry_run.into_actions();
+    let removals = actions
+        .iter()
+        .filter(|a| matches!(a, PlannedAction::Remove(_)))
+        .count();
+    let commands = actions.len() - removals;
+    let (total_size, has_more) = total_size(&notifier.planned.into_inner().unwrap());
+
+    eprintln!();
+    eprintln!(
+        "Plan: {} removal(s), {} command(s), would {} {}",
+        removals,
+        commands,
+        if moves_files { "move" } else { "reclaim" },
+        format_file_size_and_more(total_size, has_more).cyan()
+    );
+}
+
+/// Sets apart the candidates holding files tracked by git, unless
+/// `--include-tracked` is given.
+fn skip_tracked(
+    options: &OcyOptions,
+    files: Vec<RemovalCandidate>,
+) -> (Vec<RemovalCandidate>, Vec<RemovalCandidate>) {
+    if options.include_tracked {
+        return (files, Vec::new());
+    }
+    files.into_iter().partition(|c| !c.is_tracked())
+}
+
+fn print_skipped_tracked(tracked: &[RemovalCandidate]) {
+    if !tracked.is_empty() {
+        let line = format!(
+            "Skipping {} candidate(s) holding files tracked by git, see --include-tracked",
+            tracked.len()
+        );
+        eprintln!("{}", line.yellow());
+    }
+}
+
+/// Applies `--top` to the candidates found by the walk.
+fn keep_top(options: &OcyOptions, files: Vec<RemovalCandidate>) -> Vec<RemovalCandidate> {
+    match options.top {
+        Some(n) => largest(files, n),
+        None => files,
+    }
 }
 
 fn total_size(files: &[RemovalCandidate]) -> (u64, bool) {
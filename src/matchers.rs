@@ -1,5 +1,11 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{bail, Context, Result};
 use glob::Pattern;
-use ocy_core::matcher::Matcher;
+use ocy_core::{config::RulesConfig, matcher::Matcher};
 
 macro_rules! matcher {
     ($name: expr, $to_match: expr, $to_remove: expr) => {
@@ -8,6 +14,7 @@ macro_rules! matcher {
             Pattern::new($to_match).unwrap(),
             Pattern::new($to_remove).unwrap(),
         )
+        .unwrap()
     };
 }
 
@@ -35,3 +42,54 @@ pub fn standard_matchers() -> Vec<Matcher> {
         matcher_cmd!("Make", "Makefile", "make clean"),
     ]
 }
+
+/// Rule removing what git ignores, added by `--gitignore`.
+const GITIGNORE_RULE: &str = "Gitignore";
+
+/// Standard rules, with the user's rule file applied on top when present,
+/// and the rule removing what git ignores last when `gitignore` is set, so
+/// that other rules claim their targets first.
+///
+/// The rule file is `path` when given, otherwise `$XDG_CONFIG_HOME/ocy/rules.toml`
+/// (defaulting to `~/.config/ocy/rules.toml`) if it exists.
+pub fn load_matchers(path: Option<&Path>, gitignore: bool) -> Result<Vec<Matcher>> {
+    let mut matchers = load_configured_matchers(path)?;
+    if gitignore && !matchers.iter().any(|m| &*m.name == GITIGNORE_RULE) {
+        matchers.push(Matcher::with_ignored_strategy(
+            GITIGNORE_RULE.into(),
+            Pattern::new("*").unwrap(),
+            Vec::new(),
+        ));
+    }
+    Ok(matchers)
+}
+
+fn load_configured_matchers(path: Option<&Path>) -> Result<Vec<Matcher>> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match default_rules_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(standard_matchers()),
+        },
+    };
+
+    let source = fs::read_to_string(&path)
+        .wrap_err_with(|| format!("Cannot read rule file {}", path.display()))?;
+    let config = RulesConfig::parse(&source)
+        .wrap_err_with(|| format!("Invalid rule file {}", path.display()))?;
+    if !config.protected.is_empty() {
+        bail!(
+            "Invalid rule file {}: `protect` is only supported in `.ocy.toml` files",
+            path.display()
+        );
+    }
+    Ok(config.apply(standard_matchers()))
+}
+
+fn default_rules_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("ocy").join("rules.toml"))
+}
@@ -1,17 +1,30 @@
-use crate::utils::{format_opt_file_size, format_path, format_path_truncate};
+use crate::utils::{
+    format_file_size, format_file_size_and_more, format_opt_file_size, format_path,
+    format_path_truncate,
+};
 use colored::Colorize;
 use eyre::Report;
 use indicatif::{ProgressBar, ProgressStyle};
 use ocy_core::{
-    cleaner::CleanerNotifier,
+    cleaner::{CleanSummary, CleanerNotifier},
     models::{FileInfo, RemovalAction, RemovalCandidate},
-    walker::WalkNotifier,
+    walker::{SkipReason, WalkNotifier},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
 };
-use std::{cell::RefCell, path::Path, time::Duration};
 
 pub struct LoggingCleanerNotifier<'a> {
     base_path: &'a Path,
     pub progress_bar: ProgressBar,
+    /// Candidates being cleaned, when cleaning several at once.
+    running: Mutex<Vec<String>>,
 }
 
 impl<'a> LoggingCleanerNotifier<'a> {
@@ -27,26 +40,46 @@ impl<'a> LoggingCleanerNotifier<'a> {
         Self {
             base_path,
             progress_bar,
+            running: Mutex::default(),
         }
     }
-}
 
-impl<'a> CleanerNotifier for &LoggingCleanerNotifier<'a> {
-    fn notify_removal_started(&self, candidate: &RemovalCandidate) {
-        self.progress_bar.set_message(format!(
+    fn set_running(&self, candidate: &RemovalCandidate, running: bool) {
+        let label = format!(
             "{} {}",
             format_clean_action(candidate, ActionLabel::Start),
             format_candidate(self.base_path, candidate)
-        ));
+        );
+        let mut labels = self.running.lock().unwrap();
+        if running {
+            labels.push(label);
+        } else {
+            labels.retain(|l| *l != label);
+        }
+        let message = match labels.as_slice() {
+            [] => String::new(),
+            [label] => label.clone(),
+            [.., label] => format!("{} and {} more", label, labels.len() - 1),
+        };
+        self.progress_bar.set_message(message);
+    }
+}
+
+impl<'a> CleanerNotifier for &LoggingCleanerNotifier<'a> {
+    fn notify_removal_started(&self, candidate: &RemovalCandidate) {
+        self.set_running(candidate, true);
     }
 
-    fn notify_removal_success(&self, candidate: RemovalCandidate) {
+    fn notify_removal_success(&self, candidate: RemovalCandidate, freed: Option<u64>) {
+        self.set_running(&candidate, false);
         self.progress_bar.inc(1);
-        self.progress_bar.println(
+        print_line(
+            &self.progress_bar,
             format!(
-                "{} {}",
+                "{} {} ({})",
                 format_clean_action(&candidate, ActionLabel::Success),
-                format_candidate(self.base_path, &candidate)
+                format_candidate(self.base_path, &candidate),
+                format_opt_file_size(freed)
             )
             .green()
             .to_string(),
@@ -54,8 +87,10 @@ impl<'a> CleanerNotifier for &LoggingCleanerNotifier<'a> {
     }
 
     fn notify_removal_failed(&self, candidate: RemovalCandidate, report: Report) {
+        self.set_running(&candidate, false);
         self.progress_bar.inc(1);
-        self.progress_bar.println(
+        print_line(
+            &self.progress_bar,
             format!(
                 "{} {}: {}",
                 format_clean_action(&candidate, ActionLabel::Failed),
@@ -67,27 +102,111 @@ impl<'a> CleanerNotifier for &LoggingCleanerNotifier<'a> {
         );
     }
 
-    fn notify_removal_finish(&self) {
+    fn notify_removal_finish(&self, summary: &CleanSummary) {
         self.progress_bar.disable_steady_tick();
         self.progress_bar.finish_and_clear();
+        eprintln!("{}", format_summary(summary).cyan());
+    }
+}
+
+pub struct PlanCleanerNotifier<'a> {
+    base_path: &'a Path,
+    /// What removing a file would do, e.g. "Would remove".
+    removal: String,
+    pub planned: Mutex<Vec<RemovalCandidate>>,
+}
+
+impl<'a> PlanCleanerNotifier<'a> {
+    pub fn new(base_path: &'a Path, removal: String) -> Self {
+        Self {
+            base_path,
+            removal,
+            planned: Mutex::default(),
+        }
+    }
+}
+
+/// Prints the plan on stderr, leaving stdout to `--events -`.
+impl<'a> CleanerNotifier for &PlanCleanerNotifier<'a> {
+    fn notify_removal_started(&self, _candidate: &RemovalCandidate) {}
+
+    fn notify_removal_success(&self, candidate: RemovalCandidate, _freed: Option<u64>) {
+        let (action, size) = match &candidate.action {
+            RemovalAction::Delete { file_size, .. } => {
+                (self.removal.as_str(), format_opt_file_size(*file_size))
+            }
+            RemovalAction::RunCommand { .. } => ("Would run", String::new()),
+        };
+        eprintln!(
+            "{:<16} {:>9} {}",
+            action,
+            size.cyan(),
+            format_candidate(self.base_path, &candidate)
+        );
+        self.planned.lock().unwrap().push(candidate);
+    }
+
+    fn notify_removal_failed(&self, candidate: RemovalCandidate, report: Report) {
+        eprintln!(
+            "{}",
+            format!(
+                "Cannot plan {}: {}",
+                format_candidate(self.base_path, &candidate),
+                report
+            )
+            .red()
+        );
     }
+
+    fn notify_removal_finish(&self, _summary: &CleanSummary) {}
 }
 
 #[derive(Debug)]
 pub struct VecWalkNotifier<'a> {
     base_path: &'a Path,
     pub progress_bar: ProgressBar,
-    pub to_remove: RefCell<Vec<RemovalCandidate>>,
+    /// Candidates found, sorted by path once the walk is finished.
+    pub to_remove: Mutex<Vec<RemovalCandidate>>,
+    /// Index of each candidate in `to_remove` by target path, until they are
+    /// sorted. Locked with `to_remove` held.
+    positions: Mutex<HashMap<PathBuf, usize>>,
+    /// Candidates found so far, shown while walking.
+    found: AtomicUsize,
+    silent: bool,
 }
 
 impl<'a> VecWalkNotifier<'a> {
     pub fn new(base_path: &'a Path) -> Self {
         let progress_bar = ProgressBar::new_spinner();
+        progress_bar.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner} {prefix:.green} {msg}")
+                .unwrap(),
+        );
+        progress_bar.set_prefix(found_prefix(0));
         progress_bar.enable_steady_tick(Duration::from_millis(50));
+        Self::with_progress_bar(base_path, progress_bar, false)
+    }
+
+    /// Collects candidates without printing anything.
+    pub fn silent(base_path: &'a Path) -> Self {
+        Self::with_progress_bar(base_path, ProgressBar::hidden(), true)
+    }
+
+    fn with_progress_bar(base_path: &'a Path, progress_bar: ProgressBar, silent: bool) -> Self {
         Self {
             base_path,
             progress_bar,
-            to_remove: RefCell::default(),
+            to_remove: Mutex::default(),
+            positions: Mutex::default(),
+            found: AtomicUsize::new(0),
+            silent,
+        }
+    }
+
+    fn print_line(&self, line: String) {
+        if !self.silent {
+            print_line(&self.progress_bar, line);
         }
     }
 }
@@ -101,18 +220,32 @@ impl<'a> WalkNotifier for &VecWalkNotifier<'a> {
     }
 
     fn notify_candidate_for_removal(&self, candidate: RemovalCandidate) {
-        self.progress_bar.println(format!(
-            "{:>9} {:>9} {}",
-            candidate.matcher_name.green(),
-            format_opt_file_size(candidate.file_size()).cyan(),
-            format_candidate(self.base_path, &candidate),
-        ));
+        let mut to_remove = self.to_remove.lock().unwrap();
+        self.positions
+            .lock()
+            .unwrap()
+            .insert(candidate.target().path.clone(), to_remove.len());
+        to_remove.push(candidate);
+        drop(to_remove);
+        let found = self.found.fetch_add(1, Ordering::Relaxed) + 1;
+        self.progress_bar.set_prefix(found_prefix(found));
+    }
 
-        self.to_remove.borrow_mut().push(candidate);
+    fn notify_candidate_size_computed(&self, candidate: RemovalCandidate) {
+        let mut to_remove = self.to_remove.lock().unwrap();
+        let position = self
+            .positions
+            .lock()
+            .unwrap()
+            .get(&candidate.target().path)
+            .copied();
+        if let Some(position) = position {
+            to_remove[position] = candidate;
+        }
     }
 
     fn notify_fail_to_scan(&self, e: &FileInfo, report: Report) {
-        self.progress_bar.println(
+        self.print_line(
             format!(
                 "Failed to scan {}: {}",
                 format_path(self.base_path, &e.path),
@@ -123,13 +256,80 @@ impl<'a> WalkNotifier for &VecWalkNotifier<'a> {
         );
     }
 
+    fn notify_skipped(&self, file: &FileInfo, reason: SkipReason) {
+        self.print_line(
+            format!(
+                "Skipped {}: {}",
+                format_path(self.base_path, &file.path),
+                reason
+            )
+            .yellow()
+            .to_string(),
+        );
+    }
+
+    /// Lists the candidates sorted by path, as walking in parallel finds
+    /// them in no particular order.
     fn notify_walk_finish(&self) {
+        let mut to_remove = self.to_remove.lock().unwrap();
+        to_remove.sort_by(|a, b| a.target().path.cmp(&b.target().path));
+        self.positions.lock().unwrap().clear();
+        for candidate in to_remove.iter() {
+            let tracked = if candidate.is_tracked() {
+                format!(" ({} file(s) tracked by git)", candidate.tracked_files)
+                    .yellow()
+                    .to_string()
+            } else {
+                String::new()
+            };
+            self.print_line(format!(
+                "{:>9} {:>9} {}{}",
+                candidate.matcher_name.green(),
+                format_opt_file_size(candidate.file_size()).cyan(),
+                format_candidate(self.base_path, candidate),
+                tracked
+            ));
+        }
         self.progress_bar.disable_steady_tick();
         self.progress_bar.finish_and_clear();
     }
 }
 
-fn format_candidate(base_path: &Path, candidate: &RemovalCandidate) -> String {
+fn found_prefix(found: usize) -> String {
+    format!("[{} found]", found)
+}
+
+/// Prints above `progress_bar`, or as a plain line on stderr when the bar is
+/// not drawn, e.g. because stderr is not a terminal.
+fn print_line(progress_bar: &ProgressBar, line: String) {
+    if progress_bar.is_hidden() {
+        eprintln!("{}", line);
+    } else {
+        progress_bar.println(line);
+    }
+}
+
+pub fn format_summary(summary: &CleanSummary) -> String {
+    let freed = format_file_size_and_more(summary.freed, summary.has_unknown);
+    let freed = match summary.disk_freed {
+        Some(disk_freed) => format!(
+            "Freed {}, {} more available on disk",
+            freed,
+            format_file_size(disk_freed)
+        ),
+        None => format!("Freed {}", freed),
+    };
+    match summary.moved {
+        0 => freed,
+        moved => format!(
+            "Moved {} out of the way. {}",
+            format_file_size(moved),
+            freed
+        ),
+    }
+}
+
+pub fn format_candidate(base_path: &Path, candidate: &RemovalCandidate) -> String {
     match &candidate.action {
         RemovalAction::Delete { file_info, .. } => format_path(base_path, &file_info.path),
         RemovalAction::RunCommand { work_dir, command } => {
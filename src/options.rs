@@ -1,6 +1,19 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
 
 use gumdrop::Options;
+use ocy_core::{
+    filesystem::{RealFileSystem, SizeMode},
+    selection::Strategy,
+    walker::WalkOptions,
+};
+
+use crate::utils::{parse_duration, parse_size};
 
 #[derive(Debug, Options)]
 pub struct OcyOptions {
@@ -15,13 +28,351 @@ pub struct OcyOptions {
 
     #[options(short = "a", long = "all", help = "walk into hidden dirs")]
     pub walk_all: bool,
+
+    #[options(
+        no_short,
+        help = "walk into symbolic links to directories, each directory once"
+    )]
+    pub follow_symlinks: bool,
+
+    #[options(short = "x", help = "stay on the file system of the current directory")]
+    pub one_file_system: bool,
+
+    #[options(
+        no_short,
+        help = "skip projects modified within this duration (e.g. 12h, 7d, 2w)",
+        meta = "DURATION",
+        parse(try_from_str = "parse_duration")
+    )]
+    pub older_than: Option<Duration>,
+
+    #[options(
+        no_short,
+        help = "skip candidates smaller than this (e.g. 500K, 1G) or of unknown size",
+        meta = "SIZE",
+        parse(try_from_str = "parse_size")
+    )]
+    pub min_size: Option<u64>,
+
+    #[options(no_short, help = "only keep the N largest candidates", meta = "N")]
+    pub top: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "only clean enough to reclaim this much space (e.g. 20G)",
+        meta = "SIZE",
+        parse(try_from_str = "parse_size")
+    )]
+    pub reclaim: Option<u64>,
+
+    #[options(
+        no_short,
+        help = "what --reclaim and `auto` clean first, `largest` or `oldest`",
+        meta = "STRATEGY",
+        parse(try_from_str = "parse_strategy")
+    )]
+    pub strategy: Strategy,
+
+    #[options(
+        no_short,
+        help = "how to size candidates, `apparent` or `disk` (allocated blocks, like du)",
+        meta = "MODE",
+        parse(try_from_str = "parse_size_mode")
+    )]
+    pub size_mode: SizeMode,
+
+    #[options(
+        no_short,
+        help = "threads walking directories, defaults to the number of CPUs",
+        meta = "N"
+    )]
+    pub threads: Option<usize>,
+
+    #[options(
+        short = "j",
+        help = "clean on N threads, spread over candidates and the directories removed",
+        meta = "N"
+    )]
+    pub jobs: Option<usize>,
+
+    #[options(no_short, help = "also clean candidates holding files tracked by git")]
+    pub include_tracked: bool,
+
+    #[options(
+        no_short,
+        help = "also propose the directories git ignores, except secrets and IDE settings"
+    )]
+    pub gitignore: bool,
+
+    #[options(help = "load rules from this file", meta = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[options(short = "n", help = "print what would be cleaned, without cleaning")]
+    pub dry_run: bool,
+
+    #[options(help = "clean without asking for confirmation")]
+    pub yes: bool,
+
+    #[options(help = "pick the candidates to clean interactively")]
+    pub select: bool,
+
+    #[options(no_short, help = "move files to the trash instead of deleting them")]
+    pub trash: bool,
+
+    #[options(
+        no_short,
+        help = "move files to the quarantine, see `restore` and `purge`"
+    )]
+    pub quarantine: bool,
+
+    #[options(help = "output format, `text` or `json`", meta = "FORMAT")]
+    pub format: OutputFormat,
+
+    #[options(
+        no_short,
+        help = "write walk and clean events as JSON lines to this file, `-` for stdout",
+        meta = "PATH"
+    )]
+    pub events: Option<PathBuf>,
+
+    #[options(command)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown format `{}`, expected `text` or `json`", s)),
+        }
+    }
+}
+
+fn parse_size_mode(s: &str) -> Result<SizeMode, String> {
+    match s {
+        "apparent" => Ok(SizeMode::Apparent),
+        "disk" => Ok(SizeMode::DiskUsage),
+        _ => Err(format!(
+            "unknown size mode `{}`, expected `apparent` or `disk`",
+            s
+        )),
+    }
+}
+
+fn parse_strategy(s: &str) -> Result<Strategy, String> {
+    match s {
+        "largest" => Ok(Strategy::Largest),
+        "oldest" => Ok(Strategy::Oldest),
+        _ => Err(format!(
+            "unknown strategy `{}`, expected `largest` or `oldest`",
+            s
+        )),
+    }
+}
+
+#[derive(Debug, Options)]
+pub enum Command {
+    #[options(help = "restore an item from the quarantine, or list them")]
+    Restore(RestoreOptions),
+
+    #[options(help = "permanently delete items from the quarantine")]
+    Purge(PurgeOptions),
+
+    #[options(help = "browse and clean candidates in a full-screen interface")]
+    Tui(TuiOptions),
+
+    #[options(help = "clean only when the disk is running out of space")]
+    Auto(AutoOptions),
+}
+
+#[derive(Debug, Options)]
+pub struct RestoreOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(free, help = "id or original path of the item to restore")]
+    pub item: Option<String>,
+}
+
+#[derive(Debug, Options)]
+pub struct PurgeOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(
+        no_short,
+        help = "only purge items older than this (e.g. 12h, 7d, 2w)",
+        meta = "DURATION",
+        parse(try_from_str = "parse_duration")
+    )]
+    pub older_than: Option<Duration>,
+}
+
+#[derive(Debug, Options)]
+pub struct TuiOptions {
+    #[options(help = "print help message")]
+    help: bool,
+}
+
+#[derive(Debug, Options)]
+pub struct AutoOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(
+        no_short,
+        help = "clean when free space is below this (e.g. 10%, 20G)",
+        meta = "THRESHOLD"
+    )]
+    pub when_free_below: Option<Threshold>,
+
+    #[options(
+        no_short,
+        help = "stop cleaning once free space is above this, defaults to 5% more",
+        meta = "THRESHOLD"
+    )]
+    pub until_free: Option<Threshold>,
+}
+
+/// Amount of free space, absolute or relative to the size of the disk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    Percent(f64),
+    Bytes(u64),
+}
+
+impl Threshold {
+    pub fn bytes(self, total: u64) -> u64 {
+        match self {
+            Threshold::Percent(percent) => (total as f64 * percent / 100.0) as u64,
+            Threshold::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().strip_suffix('%') {
+            Some(percent) => match percent.parse() {
+                Ok(percent @ 0.0..=100.0) => Ok(Threshold::Percent(percent)),
+                _ => Err(format!("invalid percentage `{}`", s)),
+            },
+            None => parse_size(s).map(Threshold::Bytes),
+        }
+    }
 }
 
 impl OcyOptions {
+    pub fn events_on_stdout(&self) -> bool {
+        self.events.as_deref() == Some(Path::new("-"))
+    }
+
     pub fn get_ignores_set(&self) -> HashSet<PathBuf> {
         self.ignores
             .iter()
             .map(|p| p.canonicalize().unwrap())
             .collect::<HashSet<_>>()
     }
+
+    pub fn file_system(&self) -> RealFileSystem {
+        RealFileSystem::new(self.size_mode).with_one_file_system(self.one_file_system)
+    }
+
+    /// Candidates cleaned at once and threads removing each of them when
+    /// cleaning `candidates`, `--jobs` being split between the two. One
+    /// thread in all by default.
+    pub fn jobs(&self, candidates: usize) -> (usize, usize) {
+        let jobs = self.jobs.unwrap_or(1).max(1);
+        let at_once = jobs.min(candidates).max(1);
+        (at_once, jobs / at_once)
+    }
+
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            ignores: self.get_ignores_set(),
+            walk_all: self.walk_all,
+            older_than: self.older_than,
+            min_size: self.min_size,
+            project_age: self.strategy == Strategy::Oldest,
+            threads: self.threads.unwrap_or_else(|| {
+                thread::available_parallelism().map_or(1, |threads| threads.get())
+            }),
+            deferred_sizes: false,
+            follow_symlinks: self.follow_symlinks,
+            one_file_system: self.one_file_system,
+            check_git: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocy_core::{filesystem::SizeMode, selection::Strategy};
+
+    use gumdrop::Options;
+
+    use super::{parse_size_mode, parse_strategy, OcyOptions, Threshold};
+
+    #[test]
+    fn splits_jobs_between_candidates_and_removals() {
+        let jobs = |args: &[&str], candidates| {
+            OcyOptions::parse_args_default(args)
+                .unwrap()
+                .jobs(candidates)
+        };
+
+        assert_eq!(jobs(&[], 3), (1, 1));
+        assert_eq!(jobs(&["-j", "8"], 1), (1, 8));
+        assert_eq!(jobs(&["-j", "8"], 3), (3, 2));
+        assert_eq!(jobs(&["-j", "8"], 20), (8, 1));
+        assert_eq!(jobs(&["-j", "0"], 0), (1, 1));
+    }
+
+    #[test]
+    fn parses_size_modes() {
+        assert_eq!(parse_size_mode("apparent"), Ok(SizeMode::Apparent));
+        assert_eq!(parse_size_mode("disk"), Ok(SizeMode::DiskUsage));
+        assert!(parse_size_mode("blocks")
+            .unwrap_err()
+            .contains("expected `apparent` or `disk`"));
+    }
+
+    #[test]
+    fn parses_strategies() {
+        assert_eq!(parse_strategy("largest"), Ok(Strategy::Largest));
+        assert_eq!(parse_strategy("oldest"), Ok(Strategy::Oldest));
+        assert!(parse_strategy("newest")
+            .unwrap_err()
+            .contains("expected `largest` or `oldest`"));
+    }
+
+    #[test]
+    fn parses_thresholds() {
+        assert_eq!("12.5%".parse(), Ok(Threshold::Percent(12.5)));
+        assert_eq!("2G".parse(), Ok(Threshold::Bytes(2 << 30)));
+        assert!("101%"
+            .parse::<Threshold>()
+            .unwrap_err()
+            .contains("invalid percentage"));
+        assert!("half%"
+            .parse::<Threshold>()
+            .unwrap_err()
+            .contains("invalid percentage"));
+        assert!("lots".parse::<Threshold>().is_err());
+
+        assert_eq!(Threshold::Percent(25.0).bytes(1000), 250);
+        assert_eq!(Threshold::Bytes(300).bytes(1000), 300);
+    }
 }
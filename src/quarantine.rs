@@ -0,0 +1,86 @@
+use std::{path, time::SystemTime};
+
+use colored::Colorize;
+use eyre::Result;
+use ocy_core::quarantine::{Quarantine, QuarantineEntry};
+
+use crate::{
+    options::{PurgeOptions, RestoreOptions},
+    utils::{confirm, format_age, format_file_size_and_more, format_opt_file_size},
+};
+
+pub fn restore(options: RestoreOptions) -> Result<()> {
+    let quarantine = Quarantine::new()?;
+    let Some(item) = options.item else {
+        print_entries(&quarantine.entries()?);
+        return Ok(());
+    };
+
+    let is_id = quarantine.entries()?.iter().any(|e| e.id == item);
+    let query = if is_id {
+        item
+    } else {
+        path::absolute(&item)?.to_string_lossy().into_owned()
+    };
+
+    let entry = quarantine.restore(&query)?;
+    println!("{} {}", "Restored".green(), entry.original_path.display());
+    Ok(())
+}
+
+pub fn purge(options: PurgeOptions, assume_yes: bool) -> Result<()> {
+    let older_than = options.older_than.unwrap_or_default();
+    let quarantine = Quarantine::new()?;
+    let now = SystemTime::now();
+    let expired: Vec<_> = quarantine
+        .entries()?
+        .into_iter()
+        .filter(|e| {
+            now.duration_since(e.removed_at)
+                .is_ok_and(|age| age >= older_than)
+        })
+        .collect();
+
+    if expired.is_empty() {
+        println!("Nothing to purge");
+        return Ok(());
+    }
+    print_entries(&expired);
+    println!();
+
+    let (size, has_more) = total_size(&expired);
+    if confirm(
+        &format!(
+            "Permanently delete {} item(s), {} (y/N) ? ",
+            expired.len(),
+            format_file_size_and_more(size, has_more).cyan()
+        ),
+        assume_yes,
+    ) {
+        let purged = quarantine.purge(older_than)?;
+        println!("{} {} item(s)", "Purged".green(), purged.len());
+    }
+    Ok(())
+}
+
+fn print_entries(entries: &[QuarantineEntry]) {
+    if entries.is_empty() {
+        println!("The quarantine is empty");
+    }
+    for entry in entries {
+        println!(
+            "{:>12} {:>9} {:>9} {:>9} {}",
+            entry.id.yellow(),
+            entry.matcher_name.green(),
+            format_opt_file_size(entry.size).cyan(),
+            format_age(entry.removed_at),
+            entry.original_path.display()
+        );
+    }
+}
+
+fn total_size(entries: &[QuarantineEntry]) -> (u64, bool) {
+    let size = entries.iter().filter_map(|e| e.size).sum();
+    let has_more = entries.iter().any(|e| e.size.is_none());
+    (size, has_more)
+}
@@ -0,0 +1,176 @@
+use std::{
+    cmp::Reverse,
+    io::{stderr, stdin, IsTerminal},
+    path::Path,
+    process::exit,
+    sync::Arc,
+};
+
+use colored::Colorize;
+use dialoguer::MultiSelect;
+use eyre::Result;
+use ocy_core::models::RemovalCandidate;
+
+use crate::{
+    notifiers::format_candidate,
+    utils::{format_file_size_and_more, format_opt_file_size, EXIT_CONFIRMATION_UNAVAILABLE},
+};
+
+/// Lets the user pick the candidates to clean, largest first, after an
+/// optional filter on matchers. Returns no candidates when aborted.
+pub fn select_candidates(
+    base_path: &Path,
+    mut candidates: Vec<RemovalCandidate>,
+) -> Result<Vec<RemovalCandidate>> {
+    if !stdin().is_terminal() || !stderr().is_terminal() {
+        eprintln!("{}", "Cannot select candidates without a terminal".red());
+        exit(EXIT_CONFIRMATION_UNAVAILABLE);
+    }
+
+    candidates.sort_by_key(|c| Reverse(c.file_size()));
+
+    let Some(matchers) = select_matchers(&candidates)? else {
+        return Ok(Vec::new());
+    };
+    let candidates = keep_matchers(candidates, &matchers);
+
+    let items: Vec<_> = candidates
+        .iter()
+        .map(|c| {
+            format!(
+                "{:>9}  {:<12} {}",
+                format_opt_file_size(c.file_size()),
+                c.matcher_name,
+                format_candidate(base_path, c)
+            )
+        })
+        .collect();
+    let selection = MultiSelect::new()
+        .with_prompt("Select what to clean (space: toggle, a: all/none, enter: confirm)")
+        .items(&items)
+        .defaults(&vec![true; items.len()])
+        .interact_opt()?;
+
+    Ok(selection.map_or_else(Vec::new, |selection| pick(candidates, selection)))
+}
+
+/// Asks which matchers to keep when candidates come from several of them.
+fn select_matchers(candidates: &[RemovalCandidate]) -> Result<Option<Vec<Arc<str>>>> {
+    let matchers = matcher_names(candidates);
+    if matchers.len() < 2 {
+        return Ok(Some(matchers));
+    }
+
+    let items: Vec<_> = matchers
+        .iter()
+        .map(|name| {
+            let matching: Vec<_> = candidates
+                .iter()
+                .filter(|c| &c.matcher_name == name)
+                .collect();
+            let size = matching.iter().map(|c| c.estimate_file_size()).sum();
+            let has_more = matching.iter().any(|c| c.file_size().is_none());
+            format!(
+                "{:<12} {:>4} item(s) {:>10}",
+                name,
+                matching.len(),
+                format_file_size_and_more(size, has_more)
+            )
+        })
+        .collect();
+    let selection = MultiSelect::new()
+        .with_prompt("Filter by matcher (space: toggle, a: all/none, enter: confirm)")
+        .items(&items)
+        .defaults(&vec![true; items.len()])
+        .interact_opt()?;
+
+    Ok(selection.map(|selection| pick(matchers, selection)))
+}
+
+/// Names of the matchers of `candidates`, sorted, each once.
+fn matcher_names(candidates: &[RemovalCandidate]) -> Vec<Arc<str>> {
+    let mut matchers: Vec<_> = candidates.iter().map(|c| c.matcher_name.clone()).collect();
+    matchers.sort_unstable();
+    matchers.dedup();
+    matchers
+}
+
+fn keep_matchers(
+    mut candidates: Vec<RemovalCandidate>,
+    matchers: &[Arc<str>],
+) -> Vec<RemovalCandidate> {
+    candidates.retain(|c| matchers.contains(&c.matcher_name));
+    candidates
+}
+
+/// The items at the indices of `selection`, in their order in `items`.
+fn pick<T>(items: Vec<T>, mut selection: Vec<usize>) -> Vec<T> {
+    selection.sort_unstable();
+    let mut selection = selection.into_iter().peekable();
+    items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, item)| selection.next_if_eq(&i).map(|_| item))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ocy_core::models::{FileInfo, RemovalCandidate, SimpleFileKind};
+
+    use super::{keep_matchers, matcher_names, pick};
+
+    fn candidate(matcher: &str, path: &str) -> RemovalCandidate {
+        let file = FileInfo {
+            path: path.into(),
+            name: "target".into(),
+            kind: SimpleFileKind::Directory,
+            modified: None,
+            device: None,
+        };
+        RemovalCandidate::new(matcher.into(), file, Some(1))
+    }
+
+    fn paths(candidates: &[RemovalCandidate]) -> Vec<&str> {
+        candidates
+            .iter()
+            .map(|c| c.target().path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn filters_by_matcher() {
+        let candidates = vec![
+            candidate("NodeJS", "/a/node_modules"),
+            candidate("Cargo", "/b/target"),
+            candidate("NodeJS", "/c/node_modules"),
+            candidate("Maven", "/d/target"),
+        ];
+        let names = matcher_names(&candidates);
+        assert_eq!(names, [Arc::from("Cargo"), "Maven".into(), "NodeJS".into()]);
+
+        let kept = keep_matchers(candidates, &pick(names, vec![2, 0]));
+        assert_eq!(
+            paths(&kept),
+            ["/a/node_modules", "/b/target", "/c/node_modules"]
+        );
+    }
+
+    #[test]
+    fn picks_selected_indices_in_order() {
+        let candidates = vec![
+            candidate("Cargo", "/a/target"),
+            candidate("Cargo", "/b/target"),
+            candidate("Cargo", "/c/target"),
+        ];
+        assert_eq!(
+            paths(&pick(candidates.clone(), vec![2, 0])),
+            ["/a/target", "/c/target"]
+        );
+        assert!(pick(candidates.clone(), Vec::new()).is_empty());
+        // Out of range indices select nothing
+        assert_eq!(paths(&pick(candidates, vec![1, 7])), ["/b/target"]);
+    }
+}
@@ -0,0 +1,577 @@
+use std::{cmp::Reverse, path::PathBuf};
+
+use ocy_core::models::{FileInfo, RemovalAction, RemovalCandidate, SimpleFileKind};
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    widgets::TableState,
+};
+
+use crate::notifiers::format_summary;
+
+use super::AppEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Size,
+    Age,
+    Matcher,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Size => SortKey::Age,
+            SortKey::Age => SortKey::Matcher,
+            SortKey::Matcher => SortKey::Size,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Size => "size",
+            SortKey::Age => "age",
+            SortKey::Matcher => "matcher",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Idle,
+    Queued,
+    Running,
+    Done,
+    Failed(String),
+}
+
+pub struct Item {
+    pub candidate: RemovalCandidate,
+    pub marked: bool,
+    pub status: Status,
+}
+
+impl Item {
+    fn can_be_marked(&self, include_tracked: bool) -> bool {
+        (include_tracked || !self.candidate.is_tracked())
+            && matches!(self.status, Status::Idle | Status::Failed(_))
+    }
+}
+
+/// An entry of the directory shown in the contents view.
+pub struct Entry {
+    pub name: String,
+    pub kind: SimpleFileKind,
+    pub size: Option<u64>,
+}
+
+pub enum View {
+    Candidates,
+    Contents {
+        path: PathBuf,
+        entries: Option<Vec<Entry>>,
+        state: TableState,
+    },
+}
+
+/// What the event loop has to do in response to a key.
+pub enum Action {
+    ListContents(FileInfo),
+    Clean(Vec<RemovalCandidate>),
+}
+
+pub struct App {
+    pub base_path: PathBuf,
+    pub items: Vec<Item>,
+    pub state: TableState,
+    pub sort: SortKey,
+    pub scanning: Option<PathBuf>,
+    pub walking: bool,
+    pub scan_failures: usize,
+    /// Finished and total removals of the running clean.
+    pub cleaning: Option<(usize, usize)>,
+    pub confirming: bool,
+    pub view: View,
+    pub message: Option<String>,
+    pub should_quit: bool,
+    /// Whether candidates holding files tracked by git can be cleaned.
+    include_tracked: bool,
+}
+
+impl App {
+    pub fn new(base_path: PathBuf, include_tracked: bool) -> Self {
+        Self {
+            base_path,
+            items: Vec::new(),
+            state: TableState::default(),
+            sort: SortKey::Size,
+            scanning: None,
+            walking: true,
+            scan_failures: 0,
+            cleaning: None,
+            confirming: false,
+            view: View::Candidates,
+            message: None,
+            should_quit: false,
+            include_tracked,
+        }
+    }
+
+    pub fn marked(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().filter(|i| i.marked)
+    }
+
+    pub fn on_event(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::Scanning(path) => self.scanning = Some(path),
+            AppEvent::Candidate(candidate) => {
+                self.items.push(Item {
+                    candidate,
+                    marked: false,
+                    status: Status::Idle,
+                });
+                self.sort_items();
+            }
+            AppEvent::CandidateSize(candidate) => {
+                if let Some(item) = self
+                    .items
+                    .iter_mut()
+                    .find(|i| is_same_candidate(&i.candidate, &candidate))
+                {
+                    item.candidate = candidate;
+                    self.sort_items();
+                }
+            }
+            AppEvent::ScanFailed(path, error) => {
+                self.scan_failures += 1;
+                self.message = Some(format!("Failed to scan {}: {}", path.display(), error));
+            }
+            AppEvent::Skipped(path, reason) => {
+                self.message = Some(format!("Skipped {}: {}", path.display(), reason));
+            }
+            AppEvent::WalkFinished => {
+                self.walking = false;
+                self.scanning = None;
+            }
+            AppEvent::RemovalStarted(candidate) => {
+                self.set_status(&candidate, Status::Running);
+            }
+            AppEvent::RemovalFinished(candidate, result) => {
+                let status = match result {
+                    Ok(_) => Status::Done,
+                    Err(error) => Status::Failed(error),
+                };
+                self.set_status(&candidate, status);
+                if let Some((done, _)) = &mut self.cleaning {
+                    *done += 1;
+                }
+            }
+            AppEvent::CleanFinished(summary) => {
+                self.cleaning = None;
+                let failed = self
+                    .items
+                    .iter()
+                    .filter(|i| matches!(i.status, Status::Failed(_)))
+                    .count();
+                let summary = format_summary(&summary);
+                self.message = Some(match failed {
+                    0 => summary,
+                    n => format!("{}, {} item(s) failed", summary, n),
+                });
+            }
+            AppEvent::CleanAborted(error) => {
+                self.cleaning = None;
+                for item in &mut self.items {
+                    if item.status == Status::Queued {
+                        item.status = Status::Idle;
+                    }
+                }
+                self.message = Some(error);
+            }
+            AppEvent::Contents(path, listed) => {
+                if let View::Contents {
+                    path: shown,
+                    entries,
+                    ..
+                } = &mut self.view
+                {
+                    if *shown == path {
+                        *entries = Some(listed);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if self.confirming {
+            return self.on_confirm_key(key);
+        }
+        if let View::Contents { state, .. } = &mut self.view {
+            match key.code {
+                KeyCode::Esc | KeyCode::Backspace | KeyCode::Left | KeyCode::Char('q') => {
+                    self.view = View::Candidates;
+                }
+                KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+                _ => {}
+            }
+            return None;
+        }
+
+        self.message = None;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                if self.cleaning.is_some() {
+                    self.message = Some("Wait for the clean to finish before quitting".into());
+                } else {
+                    self.should_quit = true;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::Home | KeyCode::Char('g') => self.state.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.state.select_last(),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.sort_items();
+            }
+            KeyCode::Char(' ') => {
+                let include_tracked = self.include_tracked;
+                if let Some(item) = self.selected_mut() {
+                    if item.can_be_marked(include_tracked) {
+                        item.marked = !item.marked;
+                    } else if item.candidate.is_tracked() && !include_tracked {
+                        self.message = Some(format!(
+                            "{} file(s) in there are tracked by git, see --include-tracked",
+                            item.candidate.tracked_files
+                        ));
+                    }
+                }
+                self.state.select_next();
+            }
+            KeyCode::Char('a') => {
+                let mark = !self.items.iter().any(|i| i.marked);
+                for item in self
+                    .items
+                    .iter_mut()
+                    .filter(|i| i.can_be_marked(self.include_tracked))
+                {
+                    item.marked = mark;
+                }
+            }
+            KeyCode::Enter | KeyCode::Right => return self.open_selected(),
+            KeyCode::Char('c') => {
+                if self.cleaning.is_some() {
+                    self.message = Some("A clean is already running".into());
+                } else if self.marked().next().is_none() {
+                    self.message = Some("Mark items to clean with space first".into());
+                } else {
+                    self.confirming = true;
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn on_confirm_key(&mut self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                self.confirming = false;
+                let mut candidates = Vec::new();
+                for item in self.items.iter_mut().filter(|i| i.marked) {
+                    item.marked = false;
+                    item.status = Status::Queued;
+                    candidates.push(item.candidate.clone());
+                }
+                self.cleaning = Some((0, candidates.len()));
+                Some(Action::Clean(candidates))
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc | KeyCode::Char('q') => {
+                self.confirming = false;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn open_selected(&mut self) -> Option<Action> {
+        let item = self.state.selected().and_then(|i| self.items.get(i))?;
+        match &item.candidate.action {
+            RemovalAction::Delete { file_info, .. }
+                if file_info.kind == SimpleFileKind::Directory && item.status != Status::Done =>
+            {
+                let file_info = file_info.clone();
+                self.view = View::Contents {
+                    path: file_info.path.clone(),
+                    entries: None,
+                    state: TableState::default().with_selected(0),
+                };
+                Some(Action::ListContents(file_info))
+            }
+            _ => {
+                self.message = Some("Nothing to show for this item".into());
+                None
+            }
+        }
+    }
+
+    fn selected_mut(&mut self) -> Option<&mut Item> {
+        self.state.selected().and_then(|i| self.items.get_mut(i))
+    }
+
+    fn set_status(&mut self, candidate: &RemovalCandidate, status: Status) {
+        if let Some(item) = self
+            .items
+            .iter_mut()
+            .find(|i| is_same_candidate(&i.candidate, candidate))
+        {
+            item.status = status;
+        }
+    }
+
+    /// Sorts the items, keeping the same item selected.
+    fn sort_items(&mut self) {
+        let selected = self
+            .state
+            .selected()
+            .and_then(|i| self.items.get(i))
+            .map(|i| i.candidate.target().path.clone());
+
+        match self.sort {
+            SortKey::Size => self
+                .items
+                .sort_by_key(|i| Reverse(i.candidate.estimate_file_size())),
+            // Unknown ages go last, after the most recent projects.
+            SortKey::Age => self.items.sort_by_key(|i| {
+                let modified = i.candidate.project_modified;
+                (modified.is_none(), modified)
+            }),
+            SortKey::Matcher => self.items.sort_by(|a, b| {
+                a.candidate
+                    .matcher_name
+                    .cmp(&b.candidate.matcher_name)
+                    .then_with(|| a.candidate.target().path.cmp(&b.candidate.target().path))
+            }),
+        }
+
+        let position = selected.and_then(|path| {
+            self.items
+                .iter()
+                .position(|i| i.candidate.target().path == path)
+        });
+        self.state.select(position.or(Some(0)));
+    }
+}
+
+fn is_same_candidate(a: &RemovalCandidate, b: &RemovalCandidate) -> bool {
+    a.matcher_name == b.matcher_name && a.target().path == b.target().path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use ocy_core::{
+        cleaner::CleanSummary,
+        models::{FileInfo, RemovalCandidate, SimpleFileKind},
+    };
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+    use super::{Action, App, SortKey, Status, View};
+    use crate::tui::AppEvent;
+
+    fn candidate(matcher: &str, path: &str, size: u64, days_ago: u64) -> RemovalCandidate {
+        let file = FileInfo {
+            path: path.into(),
+            name: "target".into(),
+            kind: SimpleFileKind::Directory,
+            modified: None,
+            device: None,
+        };
+        let mut candidate = RemovalCandidate::new(matcher.into(), file, Some(size));
+        candidate.project_modified =
+            Some(SystemTime::now() - Duration::from_secs(days_ago * 24 * 60 * 60));
+        candidate
+    }
+
+    /// An app listing a large recent Cargo target, a medium old Maven one and
+    /// a small NodeJS one of middle age, the first one selected.
+    fn app() -> App {
+        let mut app = App::new(PathBuf::from("/p"), false);
+        for candidate in [
+            candidate("NodeJS", "/p/web/node_modules", 10, 5),
+            candidate("Cargo", "/p/cli/target", 300, 1),
+            candidate("Maven", "/p/api/target", 20, 30),
+        ] {
+            app.on_event(AppEvent::Candidate(candidate));
+        }
+        app.on_event(AppEvent::WalkFinished);
+        press(&mut app, KeyCode::Home);
+        app
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.on_key(KeyEvent::from(code))
+    }
+
+    fn paths(app: &App) -> Vec<&str> {
+        app.items
+            .iter()
+            .map(|i| i.candidate.target().path.to_str().unwrap())
+            .collect()
+    }
+
+    fn selected(app: &App) -> &str {
+        let item = &app.items[app.state.selected().unwrap()];
+        item.candidate.target().path.to_str().unwrap()
+    }
+
+    #[test]
+    fn sorts_by_size_age_and_matcher_keeping_the_selection() {
+        let mut app = app();
+        assert!(!app.walking);
+        assert_eq!(
+            paths(&app),
+            ["/p/cli/target", "/p/api/target", "/p/web/node_modules"]
+        );
+        press(&mut app, KeyCode::Down);
+        assert_eq!(selected(&app), "/p/api/target");
+
+        press(&mut app, KeyCode::Char('s'));
+        assert_eq!(app.sort, SortKey::Age);
+        assert_eq!(
+            paths(&app),
+            ["/p/api/target", "/p/web/node_modules", "/p/cli/target"]
+        );
+        assert_eq!(selected(&app), "/p/api/target");
+
+        press(&mut app, KeyCode::Char('s'));
+        assert_eq!(app.sort, SortKey::Matcher);
+        assert_eq!(
+            paths(&app),
+            ["/p/cli/target", "/p/api/target", "/p/web/node_modules"]
+        );
+        assert_eq!(selected(&app), "/p/api/target");
+    }
+
+    #[test]
+    fn marks_and_unmarks_items() {
+        let mut app = app();
+        press(&mut app, KeyCode::Char(' '));
+        assert!(app.items[0].marked);
+        assert_eq!(selected(&app), "/p/api/target");
+
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(app.marked().count(), 0);
+
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.marked().count(), 3);
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.marked().count(), 0);
+    }
+
+    #[test]
+    fn tracked_items_cannot_be_marked() {
+        let mut app = App::new(PathBuf::from("/p"), false);
+        let mut tracked = candidate("Cargo", "/p/cli/target", 300, 1);
+        tracked.tracked_files = 2;
+        app.on_event(AppEvent::Candidate(tracked));
+
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(app.marked().count(), 0);
+        assert!(app.message.as_ref().unwrap().contains("tracked by git"));
+    }
+
+    #[test]
+    fn cleans_marked_items_once_confirmed() {
+        let mut app = app();
+        press(&mut app, KeyCode::Char('c'));
+        assert!(!app.confirming);
+        assert!(app.message.as_ref().unwrap().contains("Mark items"));
+
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char('c'));
+        assert!(app.confirming);
+        assert!(press(&mut app, KeyCode::Char('n')).is_none());
+        assert!(!app.confirming);
+        assert!(app.items[0].marked);
+
+        press(&mut app, KeyCode::Char('c'));
+        let Some(Action::Clean(candidates)) = press(&mut app, KeyCode::Char('y')) else {
+            panic!("should clean");
+        };
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(app.items[0].status, Status::Queued);
+        assert!(!app.items[0].marked);
+        assert_eq!(app.cleaning, Some((0, 1)));
+
+        press(&mut app, KeyCode::Char('q'));
+        assert!(!app.should_quit);
+
+        let cleaned = candidates[0].clone();
+        app.on_event(AppEvent::RemovalStarted(cleaned.clone()));
+        assert_eq!(app.items[0].status, Status::Running);
+        app.on_event(AppEvent::RemovalFinished(cleaned, Ok(Some(300))));
+        assert_eq!(app.items[0].status, Status::Done);
+        assert_eq!(app.cleaning, Some((1, 1)));
+        app.on_event(AppEvent::CleanFinished(CleanSummary {
+            freed: 300,
+            ..CleanSummary::default()
+        }));
+        assert_eq!(app.cleaning, None);
+        assert!(app.message.as_ref().unwrap().starts_with("Freed"));
+
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn failed_and_aborted_items_can_be_marked_again() {
+        let mut app = app();
+        press(&mut app, KeyCode::Char('a'));
+        press(&mut app, KeyCode::Char('c'));
+        let Some(Action::Clean(candidates)) = press(&mut app, KeyCode::Char('y')) else {
+            panic!("should clean");
+        };
+        app.on_event(AppEvent::RemovalFinished(
+            candidates[0].clone(),
+            Err("denied".into()),
+        ));
+        app.on_event(AppEvent::CleanAborted("Cannot locate the trash".into()));
+
+        assert_eq!(app.items[0].status, Status::Failed("denied".into()));
+        assert_eq!(app.items[1].status, Status::Idle);
+        assert_eq!(app.cleaning, None);
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.marked().count(), 3);
+    }
+
+    #[test]
+    fn shows_the_contents_of_the_selected_item() {
+        let mut app = app();
+        let Some(Action::ListContents(dir)) = press(&mut app, KeyCode::Enter) else {
+            panic!("should list contents");
+        };
+        assert_eq!(dir.path, PathBuf::from("/p/cli/target"));
+        app.on_event(AppEvent::Contents(dir.path.clone(), Vec::new()));
+        assert!(matches!(
+            &app.view,
+            View::Contents {
+                entries: Some(_),
+                ..
+            }
+        ));
+
+        // Keys browse the contents, not the candidates
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(app.marked().count(), 0);
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.view, View::Candidates));
+        assert!(!app.should_quit);
+    }
+}
@@ -0,0 +1,171 @@
+mod app;
+mod notifiers;
+mod ui;
+
+use std::{
+    cmp::Reverse,
+    io::{stdout, IsTerminal},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use eyre::{bail, Context, Result};
+use ocy_core::{
+    cleaner::{CleanSummary, Cleaner},
+    command::RealCommandExecutor,
+    filesystem::{FileSystem, FileSystemClean, RealFileSystem, SizeMode},
+    models::{FileInfo, RemovalCandidate},
+    quarantine::Quarantine,
+    walker::{SkipReason, WalkOptions, Walker},
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyEventKind},
+    DefaultTerminal,
+};
+
+use crate::{matchers::load_matchers, options::OcyOptions, trash_file_system};
+
+use app::{Action, App, Entry};
+use notifiers::{ChannelCleanerNotifier, ChannelWalkNotifier};
+
+/// Messages sent to the UI thread by the walker, the cleaner and the
+/// threads listing directory contents.
+pub enum AppEvent {
+    Scanning(PathBuf),
+    Candidate(RemovalCandidate),
+    /// A candidate sent earlier, now with its size.
+    CandidateSize(RemovalCandidate),
+    ScanFailed(PathBuf, String),
+    Skipped(PathBuf, SkipReason),
+    WalkFinished,
+    RemovalStarted(RemovalCandidate),
+    /// Bytes freed, when known, or the error.
+    RemovalFinished(RemovalCandidate, Result<Option<u64>, String>),
+    CleanFinished(CleanSummary),
+    CleanAborted(String),
+    Contents(PathBuf, Vec<Entry>),
+}
+
+/// Runs the full-screen interface: candidates stream in while walking, and
+/// marked ones are cleaned in the background.
+pub fn run(options: &OcyOptions) -> Result<()> {
+    if !stdout().is_terminal() {
+        bail!("The TUI needs a terminal");
+    }
+
+    // Candidates are listed right away, their sizes fill in later. Their
+    // project age is shown and sorted on.
+    let walk_options = WalkOptions {
+        deferred_sizes: true,
+        project_age: true,
+        ..options.walk_options()
+    };
+    let fs = options.file_system();
+    let matchers = load_matchers(options.config.as_deref(), options.gitignore)?;
+    let current_directory = RealFileSystem::default()
+        .current_directory()
+        .wrap_err("Cannot scan current directory")?;
+
+    let (tx, rx) = mpsc::channel();
+    let walk_tx = tx.clone();
+    let walk_directory = current_directory.clone();
+    thread::spawn(move || {
+        let notifier = ChannelWalkNotifier::new(walk_tx);
+        Walker::new(fs, matchers, notifier, walk_options).walk_from_path(&walk_directory);
+    });
+
+    let app = App::new(current_directory.path, options.include_tracked);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, app, options, tx, rx);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut app: App,
+    options: &OcyOptions,
+    tx: Sender<AppEvent>,
+    rx: Receiver<AppEvent>,
+) -> Result<()> {
+    while !app.should_quit {
+        terminal.draw(|frame| ui::draw(frame, &mut app))?;
+
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    match app.on_key(key) {
+                        Some(Action::ListContents(dir)) => {
+                            spawn_list_contents(dir, options.size_mode, tx.clone())
+                        }
+                        Some(Action::Clean(candidates)) => {
+                            spawn_clean(options, candidates, tx.clone())
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+
+        while let Ok(event) = rx.try_recv() {
+            app.on_event(event);
+        }
+    }
+    Ok(())
+}
+
+fn spawn_clean(options: &OcyOptions, candidates: Vec<RemovalCandidate>, tx: Sender<AppEvent>) {
+    let trash = options.trash;
+    let quarantine = options.quarantine;
+    let (jobs, removal_jobs) = options.jobs(candidates.len());
+    let real_fs = options.file_system().with_jobs(removal_jobs);
+    thread::spawn(move || {
+        let result = if trash {
+            trash_file_system(real_fs).map(|fs| clean(candidates, fs, jobs, tx.clone()))
+        } else if quarantine {
+            Quarantine::new()
+                .wrap_err("Cannot locate the quarantine")
+                .map(|fs| clean(candidates, fs.with_sizes(real_fs), jobs, tx.clone()))
+        } else {
+            clean(candidates, real_fs, jobs, tx.clone());
+            Ok(())
+        };
+        if let Err(report) = result {
+            let _ = tx.send(AppEvent::CleanAborted(format!("{:#}", report)));
+        }
+    });
+}
+
+fn clean(
+    candidates: Vec<RemovalCandidate>,
+    fs: impl FileSystemClean,
+    jobs: usize,
+    tx: Sender<AppEvent>,
+) {
+    // Commands must not write over the screen.
+    let ce = RealCommandExecutor::capturing();
+    Cleaner::new(candidates, fs, ce, ChannelCleanerNotifier::new(tx))
+        .with_jobs(jobs)
+        .clean();
+}
+
+/// Lists `dir` with the size of each entry, largest first.
+fn spawn_list_contents(dir: FileInfo, size_mode: SizeMode, tx: Sender<AppEvent>) {
+    thread::spawn(move || {
+        let fs = RealFileSystem::new(size_mode);
+        let mut entries: Vec<_> = fs
+            .list_files(&dir)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file| Entry {
+                size: fs.file_size(&file).ok(),
+                kind: file.kind,
+                name: file.name,
+            })
+            .collect();
+        entries.sort_by_key(|e| Reverse(e.size));
+        let _ = tx.send(AppEvent::Contents(dir.path, entries));
+    });
+}
@@ -0,0 +1,91 @@
+use std::sync::mpsc::Sender;
+
+use eyre::Report;
+use ocy_core::{
+    cleaner::{CleanSummary, CleanerNotifier},
+    models::{FileInfo, RemovalCandidate},
+    walker::{SkipReason, WalkNotifier},
+};
+
+use super::AppEvent;
+
+/// Forwards walk notifications to the UI thread.
+pub struct ChannelWalkNotifier {
+    tx: Sender<AppEvent>,
+}
+
+impl ChannelWalkNotifier {
+    pub fn new(tx: Sender<AppEvent>) -> Self {
+        Self { tx }
+    }
+
+    // The UI may be gone already, there is nobody left to notify then.
+    fn send(&self, event: AppEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+impl WalkNotifier for ChannelWalkNotifier {
+    fn notify_entered_directory(&self, dir: &FileInfo) {
+        self.send(AppEvent::Scanning(dir.path.clone()));
+    }
+
+    fn notify_candidate_for_removal(&self, candidate: RemovalCandidate) {
+        self.send(AppEvent::Candidate(candidate));
+    }
+
+    fn notify_candidate_size_computed(&self, candidate: RemovalCandidate) {
+        self.send(AppEvent::CandidateSize(candidate));
+    }
+
+    fn notify_fail_to_scan(&self, e: &FileInfo, report: Report) {
+        self.send(AppEvent::ScanFailed(
+            e.path.clone(),
+            format!("{:#}", report),
+        ));
+    }
+
+    fn notify_skipped(&self, file: &FileInfo, reason: SkipReason) {
+        self.send(AppEvent::Skipped(file.path.clone(), reason));
+    }
+
+    fn notify_walk_finish(&self) {
+        self.send(AppEvent::WalkFinished);
+    }
+}
+
+/// Forwards clean notifications to the UI thread.
+pub struct ChannelCleanerNotifier {
+    tx: Sender<AppEvent>,
+}
+
+impl ChannelCleanerNotifier {
+    pub fn new(tx: Sender<AppEvent>) -> Self {
+        Self { tx }
+    }
+
+    fn send(&self, event: AppEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+impl CleanerNotifier for ChannelCleanerNotifier {
+    fn notify_removal_started(&self, candidate: &RemovalCandidate) {
+        self.send(AppEvent::RemovalStarted(candidate.clone()));
+    }
+
+    fn notify_removal_success(&self, candidate: RemovalCandidate, freed: Option<u64>) {
+        self.send(AppEvent::RemovalFinished(candidate, Ok(freed)));
+    }
+
+    fn notify_removal_failed(&self, candidate: RemovalCandidate, report: Report) {
+        self.send(AppEvent::RemovalFinished(
+            candidate,
+            Err(format!("{:#}", report)),
+        ));
+    }
+
+    fn notify_removal_finish(&self, summary: &CleanSummary) {
+        self.send(AppEvent::CleanFinished(*summary));
+    }
+}
@@ -0,0 +1,208 @@
+use ocy_core::models::SimpleFileKind;
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, Gauge, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+use crate::{
+    notifiers::format_candidate,
+    utils::{format_age, format_file_size_and_more, format_opt_file_size, format_path},
+};
+
+use super::app::{App, Entry, Item, Status, View};
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, status, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(header_line(app), header);
+    if let View::Contents {
+        path,
+        entries,
+        state,
+    } = &mut app.view
+    {
+        let title = format!(" {} ", format_path(&app.base_path, path));
+        draw_contents(frame, body, title, entries.as_deref(), state);
+    } else {
+        draw_candidates(frame, body, app);
+    }
+    draw_status(frame, status, app);
+    frame.render_widget(help_line(app), help);
+
+    if app.confirming {
+        draw_confirm(frame, app);
+    }
+}
+
+fn header_line(app: &App) -> Line<'static> {
+    let (size, has_more) = total_size(app.items.iter());
+    let (marked_size, marked_has_more) = total_size(app.marked());
+    Line::from(format!(
+        " ocy · {} candidate(s), {} · {} marked, {} · sorted by {}",
+        app.items.len(),
+        format_file_size_and_more(size, has_more),
+        app.marked().count(),
+        format_file_size_and_more(marked_size, marked_has_more),
+        app.sort.label()
+    ))
+    .bold()
+}
+
+fn draw_candidates(frame: &mut Frame, area: Rect, app: &mut App) {
+    let rows = app.items.iter().map(|item| {
+        let (status, style) = match &item.status {
+            Status::Idle if item.candidate.is_tracked() => {
+                ("tracked", Style::default().fg(Color::DarkGray))
+            }
+            Status::Idle => ("", Style::default()),
+            Status::Queued => ("queued", Style::default().fg(Color::DarkGray)),
+            Status::Running => ("running", Style::default().fg(Color::Yellow)),
+            Status::Done => ("cleaned", Style::default().fg(Color::Green)),
+            Status::Failed(_) => ("failed", Style::default().fg(Color::Red)),
+        };
+        Row::new(vec![
+            if item.marked { "[x]" } else { "[ ]" }.to_string(),
+            format_opt_file_size(item.candidate.file_size()),
+            item.candidate
+                .project_modified
+                .map(format_age)
+                .unwrap_or_default(),
+            item.candidate.matcher_name.to_string(),
+            format_candidate(&app.base_path, &item.candidate),
+            status.to_string(),
+        ])
+        .style(style)
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(3),
+            Constraint::Length(10),
+            Constraint::Length(9),
+            Constraint::Length(12),
+            Constraint::Fill(1),
+            Constraint::Length(8),
+        ],
+    )
+    .header(Row::new(["", "Size", "Modified", "Matcher", "Path", "Status"]).underlined())
+    .block(Block::bordered().title(" Candidates "))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, area, &mut app.state);
+}
+
+fn draw_contents(
+    frame: &mut Frame,
+    area: Rect,
+    title: String,
+    entries: Option<&[Entry]>,
+    state: &mut TableState,
+) {
+    let block = Block::bordered().title(title);
+    let Some(entries) = entries else {
+        frame.render_widget(Paragraph::new("Computing sizes…").block(block), area);
+        return;
+    };
+    let rows = entries.iter().map(|entry| {
+        let name = match entry.kind {
+            SimpleFileKind::Directory => format!("{}/", entry.name),
+            SimpleFileKind::File => entry.name.clone(),
+            SimpleFileKind::Symlink => format!("{}@", entry.name),
+        };
+        Row::new(vec![format_opt_file_size(entry.size), name])
+    });
+    let table = Table::new(rows, [Constraint::Length(10), Constraint::Fill(1)])
+        .header(Row::new(["Size", "Name"]).underlined())
+        .block(block)
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, area, state);
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, app: &App) {
+    if let Some((done, total)) = app.cleaning {
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(done as f64 / total.max(1) as f64)
+            .label(format!("Cleaning {}/{}", done, total));
+        frame.render_widget(gauge, area);
+        return;
+    }
+
+    let line = if let Some(message) = &app.message {
+        Line::from(format!(" {}", message)).yellow()
+    } else if let Some(failed) = selected_failure(app) {
+        Line::from(format!(" {}", failed)).red()
+    } else if let Some(path) = &app.scanning {
+        Line::from(format!(" Scanning {}", format_path(&app.base_path, path)))
+    } else if app.walking {
+        Line::from(" Scanning…")
+    } else if app.scan_failures > 0 {
+        Line::from(format!(
+            " Walk finished, {} director(ies) could not be scanned",
+            app.scan_failures
+        ))
+    } else {
+        Line::from(" Walk finished")
+    };
+    frame.render_widget(line, area);
+}
+
+fn selected_failure(app: &App) -> Option<&str> {
+    if !matches!(app.view, View::Candidates) {
+        return None;
+    }
+    let item = app.state.selected().and_then(|i| app.items.get(i))?;
+    match &item.status {
+        Status::Failed(error) => Some(error),
+        _ => None,
+    }
+}
+
+fn help_line(app: &App) -> Line<'static> {
+    let help = match app.view {
+        View::Candidates => {
+            " ↑↓ move · space mark · a mark all · s sort · enter open · c clean · q quit"
+        }
+        View::Contents { .. } => " ↑↓ move · esc back",
+    };
+    Line::from(help).dark_gray()
+}
+
+fn draw_confirm(frame: &mut Frame, app: &App) {
+    let (size, has_more) = total_size(app.marked());
+    let text = format!(
+        "Clean {} item(s) and reclaim {} ?\n\n(y/N)",
+        app.marked().count(),
+        format_file_size_and_more(size, has_more)
+    );
+    let [area] = Layout::horizontal([Constraint::Length(50)])
+        .flex(Flex::Center)
+        .areas(frame.area());
+    let [area] = Layout::vertical([Constraint::Length(5)])
+        .flex(Flex::Center)
+        .areas(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(text)
+            .centered()
+            .block(Block::bordered().title(" Confirm ")),
+        area,
+    );
+}
+
+fn total_size<'a>(items: impl Iterator<Item = &'a Item>) -> (u64, bool) {
+    items.fold((0, false), |(size, has_more), item| {
+        (
+            size + item.candidate.estimate_file_size(),
+            has_more || item.candidate.file_size().is_none(),
+        )
+    })
+}
@@ -1,5 +1,10 @@
+use colored::Colorize;
 use indicatif::HumanBytes;
-use std::{io::Write, path::Path};
+use std::{
+    io::{IsTerminal, Write},
+    path::Path,
+    time::{Duration, SystemTime},
+};
 
 pub fn format_opt_file_size(size: Option<u64>) -> String {
     if let Some(size) = size {
@@ -22,15 +27,49 @@ pub fn format_file_size(size: u64) -> String {
     HumanBytes(size).to_string()
 }
 
-pub fn prompt(message: &str) -> bool {
-    print!("{}", message);
-    std::io::stdout().flush().unwrap();
+/// Exit code used when confirmation is needed but cannot be asked.
+pub const EXIT_CONFIRMATION_UNAVAILABLE: i32 = 3;
 
-    let mut buffer = String::new();
+/// Asks a yes/no question on stderr, keeping stdout free for reports.
+///
+/// Returns `None` when no answer can be read, because stdin is not a
+/// terminal or is closed.
+pub fn prompt(message: &str) -> Option<bool> {
     let stdin = std::io::stdin();
-    stdin.read_line(&mut buffer).unwrap();
+    if !stdin.is_terminal() {
+        return None;
+    }
 
-    buffer.trim().to_ascii_lowercase() == "y"
+    eprint!("\n{}", message);
+    std::io::stderr().flush().ok()?;
+
+    let mut buffer = String::new();
+    match stdin.read_line(&mut buffer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buffer.trim().eq_ignore_ascii_case("y")),
+    }
+}
+
+/// Returns `true` when `assume_yes` is set or the user agrees, and exits
+/// with [`EXIT_CONFIRMATION_UNAVAILABLE`] when the user cannot be asked.
+pub fn confirm(message: &str, assume_yes: bool) -> bool {
+    if assume_yes {
+        return true;
+    }
+    prompt(message).unwrap_or_else(|| {
+        eprintln!(
+            "{}",
+            "Cannot ask for confirmation without a terminal, use --yes".red()
+        );
+        std::process::exit(EXIT_CONFIRMATION_UNAVAILABLE)
+    })
+}
+
+/// Disables colours unless both stdout and stderr are terminals.
+pub fn configure_colors() {
+    if !std::io::stdout().is_terminal() || !std::io::stderr().is_terminal() {
+        colored::control::set_override(false);
+    }
 }
 
 pub fn format_path(base_path: &Path, p: &Path) -> String {
@@ -50,3 +89,99 @@ pub fn format_path_truncate(base_path: &Path, p: &Path) -> String {
 fn try_relativize_path<'a>(base_path: &'a Path, path: &'a Path) -> &'a Path {
     path.strip_prefix(base_path).unwrap_or(path)
 }
+
+/// Parses durations such as `90s`, `30m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`", s))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid duration `{}`, use s, m, h, d or w", s)),
+    };
+    value
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration `{}` is too long", s))
+}
+
+/// Parses sizes such as `512`, `20K`, `1.5g` or `300MiB`, in binary units.
+/// Units are case-insensitive.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value.parse().map_err(|_| format!("invalid size `{}`", s))?;
+    let unit = unit.to_ascii_uppercase();
+    let exponent = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(format!("invalid size `{}`, use K, M, G or T", s)),
+    };
+    Ok((value * 1024f64.powi(exponent)) as u64)
+}
+
+pub fn format_age(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+    match secs {
+        0..=119 => format!("{}s ago", secs),
+        120..=7199 => format!("{}m ago", secs / 60),
+        7200..=172_799 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_duration, parse_size};
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(
+            parse_duration(" 2w "),
+            Ok(Duration::from_secs(14 * 24 * 3600))
+        );
+        assert!(parse_duration("3y")
+            .unwrap_err()
+            .contains("use s, m, h, d or w"));
+        assert!(parse_duration("d")
+            .unwrap_err()
+            .contains("invalid duration"));
+        assert!(parse_duration("99999999999999999w")
+            .unwrap_err()
+            .contains("too long"));
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("20K"), Ok(20 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size(" 300MiB "), Ok(300 << 20));
+        assert_eq!(parse_size("2TB"), Ok(2 << 40));
+        assert_eq!(parse_size("500m"), Ok(500 << 20));
+        assert_eq!(parse_size("20k"), Ok(20 * 1024));
+        assert_eq!(parse_size("1gib"), Ok(1 << 30));
+        assert_eq!(parse_size("2tb"), Ok(2 << 40));
+        assert!(parse_size("3P").unwrap_err().contains("use K, M, G or T"));
+        assert!(parse_size("G").unwrap_err().contains("invalid size"));
+    }
+}